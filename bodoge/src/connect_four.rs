@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use clap::Args;
+use connect_four::{generic_search, BitBoard, Board, BoardPlayout, McTreeAI, Playout, Side};
+use game::{mcts::Mcts, negamax::Negamax};
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::play::{parse_param, replay, Ai, Common, Table};
+
+/// The opening.
+#[derive(Debug, Clone, Args)]
+pub struct Variant {
+    /// A board as the JSON object of the web UI, `{"cols": [["A", "B"], ...]}`,
+    /// played on from there instead of the empty board.
+    #[arg(long)]
+    pub position: Option<String>,
+    /// Columns played before the game begins, separated by commas.
+    #[arg(long, value_delimiter = ',')]
+    pub moves: Vec<usize>,
+}
+
+/// Reads a board as the JSON object of the web UI.
+pub fn parse_position(json: &str) -> Result<BitBoard, String> {
+    let board: Board = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(BitBoard::from(board))
+}
+
+/// The playout of the `generic` AI and of the engine.
+pub fn playout(seed: u64) -> BoardPlayout<SmallRng> {
+    BoardPlayout::new(SmallRng::seed_from_u64(seed), Playout::Tactical)
+}
+
+impl Variant {
+    pub fn start(&self) -> Result<BitBoard, String> {
+        let mut board = match &self.position {
+            Some(position) => parse_position(position)?,
+            None => BitBoard::new(),
+        };
+        replay(&mut board, &self.moves)?;
+        Ok(board)
+    }
+
+    /// The rules of a game from the empty board.
+    pub fn rules(&self) -> Result<connect_four::Variant, String> {
+        if self.position.is_some() {
+            return Err("--position has no record of how it was reached".to_owned());
+        }
+        Ok(connect_four::Variant {})
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Options {
+    #[command(flatten)]
+    pub variant: Variant,
+    #[command(flatten)]
+    pub common: Common,
+    /// `mctree:<limit ms>:<expansion threshold>:<c>`, `generic:<limit ms>:<c>`
+    /// or `negamax:<depth>:<limit ms>`. `mctree-n:<playouts>:<expansion
+    /// threshold>:<c>` and `generic-n:<playouts>:<c>` run a number of playouts
+    /// instead, so that seeded games replay the same.
+    #[arg(long, default_value = "mctree:1000:2:2.0")]
+    pub ai: String,
+}
+
+/// Rows from the top with `A`, `B` and `.`, under the numbers of the columns.
+pub fn render(board: &BitBoard) -> String {
+    let mut lines = vec!["0 1 2 3 4 5 6".to_owned()];
+    for row in (0..6).rev() {
+        let cells = (0..7)
+            .map(|col| match board.get(col, row) {
+                Some(Side::A) => "A",
+                Some(Side::B) => "B",
+                None => ".",
+            })
+            .collect::<Vec<_>>();
+        lines.push(cells.join(" "));
+    }
+    lines.join("\n")
+}
+
+fn parse_playouts(value: &str) -> Result<u32, String> {
+    match parse_param("playouts", value)? {
+        0 => Err("playouts must be positive".to_owned()),
+        playouts => Ok(playouts),
+    }
+}
+
+pub fn build_ai(spec: &str, seed: Option<u64>) -> Result<Ai<BitBoard>, String> {
+    let rng = match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    };
+    let words = spec.split(':').collect::<Vec<_>>();
+    match words[..] {
+        ["mctree", limit, expansion_threshold, c] => {
+            let mut ai = McTreeAI::new(
+                rng,
+                parse_param("limit", limit)?,
+                parse_param("expansion threshold", expansion_threshold)?,
+                parse_param("c", c)?,
+            );
+            Ok(Box::new(move |board: &BitBoard| {
+                Ok(ai.search(board).map(|r| r.col).into_iter().collect())
+            }))
+        }
+        ["mctree-n", playouts, expansion_threshold, c] => {
+            let playouts = parse_playouts(playouts)?;
+            let mut ai = McTreeAI::new(
+                rng,
+                0,
+                parse_param("expansion threshold", expansion_threshold)?,
+                parse_param("c", c)?,
+            );
+            Ok(Box::new(move |board: &BitBoard| {
+                ai.reset(board);
+                ai.run(playouts);
+                // Too few playouts to expand the root, any legal move will do.
+                let col = ai
+                    .best()
+                    .map(|r| r.col)
+                    .or_else(|| board.list_can_put().first().copied());
+                Ok(col.into_iter().collect())
+            }))
+        }
+        ["generic", limit, c] => {
+            let limit = Duration::from_millis(parse_param("limit", limit)?);
+            let mut mcts = Mcts::new(
+                &BitBoard::new(),
+                BoardPlayout::new(rng, Playout::Tactical),
+                parse_param("c", c)?,
+            );
+            Ok(Box::new(move |board: &BitBoard| {
+                mcts.reset(board);
+                Ok(generic_search(&mut mcts, limit)
+                    .map(|r| r.col)
+                    .into_iter()
+                    .collect())
+            }))
+        }
+        ["generic-n", playouts, c] => {
+            let playouts = parse_playouts(playouts)?;
+            let mut mcts = Mcts::new(
+                &BitBoard::new(),
+                BoardPlayout::new(rng, Playout::Tactical),
+                parse_param("c", c)?,
+            );
+            Ok(Box::new(move |board: &BitBoard| {
+                mcts.reset(board);
+                mcts.run(playouts);
+                let col = mcts
+                    .best()
+                    .map(|best| best.mv)
+                    .or_else(|| board.list_can_put().first().copied());
+                Ok(col.into_iter().collect())
+            }))
+        }
+        ["negamax", depth, limit] => {
+            let mut negamax = Negamax::new(BitBoard::evaluate, parse_param("depth", depth)?)
+                .with_time_limit(Duration::from_millis(parse_param("limit", limit)?));
+            Ok(Box::new(move |board: &BitBoard| {
+                Ok(negamax.search(board).best.into_iter().collect())
+            }))
+        }
+        _ => Err(format!("unknown ai: {}", spec)),
+    }
+}
+
+pub fn table(options: &Options) -> Result<Table<BitBoard>, String> {
+    Ok(Table {
+        game: options.variant.start()?,
+        render,
+        names: ["A", "B"],
+        human: options.common.human,
+        ai: build_ai(&options.ai, options.common.seed)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::play::Human;
+
+    #[test]
+    fn render_board() {
+        let mut board = BitBoard::new();
+        for col in [3, 3, 4] {
+            board.put(col, board.calc_next());
+        }
+        let text = render(&board);
+        assert!(text.starts_with("0 1 2 3 4 5 6\n"));
+        assert!(text.ends_with(". . . B . . .\n. . . A A . ."));
+    }
+
+    #[test]
+    fn ais() {
+        for ai in [
+            "mctree:10:2:2.0",
+            "mctree-n:500:2:2.0",
+            "generic:10:2.0",
+            "generic-n:500:2.0",
+            "negamax:4:100",
+        ] {
+            let mut ai = build_ai(ai, Some(1)).unwrap();
+            let mut board = BitBoard::new();
+            for col in [0, 6, 1, 6, 2, 5] {
+                board.put(col, board.calc_next());
+            }
+            assert_eq!(ai(&board).unwrap(), vec![3]);
+        }
+        assert!(build_ai("mctree:10:2", None).is_err());
+        assert!(build_ai("mctree-n:0:2:2.0", None).is_err());
+
+        // A single playout does not expand the root.
+        let mut ai = build_ai("mctree-n:1:2:2.0", Some(1)).unwrap();
+        assert_eq!(ai(&BitBoard::new()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reproducible() {
+        let play = || {
+            let mut table = table(&Options {
+                variant: Variant {
+                    position: None,
+                    moves: vec![],
+                },
+                common: Common {
+                    human: Human::None,
+                    seed: Some(3),
+                },
+                ai: "mctree-n:300:2:2.0".to_owned(),
+            })
+            .unwrap();
+            let mut output = Vec::new();
+            table.play(&mut "".as_bytes(), &mut output).unwrap();
+            output
+        };
+        assert_eq!(play(), play());
+    }
+}
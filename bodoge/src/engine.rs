@@ -0,0 +1,388 @@
+//! A line protocol in the spirit of UCI and GTP to drive the MCTS engines from
+//! other programs. One command per line:
+//!
+//! - `isready`: answers `readyok`.
+//! - `position startpos [moves <move>...]` or `position json <board> [moves
+//!   <move>...]`: sets the position. `startpos` is the start given on the command
+//!   line and `<board>` the JSON object of the web UI.
+//! - `setoption <name> <value>`: `time` in ms per `go`, `seed` as a number or
+//!   `random`, the exploration constant `c`, or `info` in ms between info lines.
+//! - `go [time <ms>]`: searches the position and answers `bestmove <move>`, or
+//!   `bestmove none` when the game is over.
+//! - `analyze`: searches until `stop` or until the position is solved.
+//! - `stop`: ends the search, which answers `bestmove` at once.
+//! - `quit`.
+//!
+//! While searching, the engine reports the most promising move with its mean
+//! point for the side to move, and the proven result once known:
+//!
+//! ```text
+//! info time 500 visits 41234 nodes 38211 best 3 point 0.612
+//! info time 612 visits 50112 nodes 47710 best 3 point 1.000 proof win 7
+//! bestmove 3
+//! ```
+//!
+//! Bad commands are answered with `error <message>` and change nothing. At the
+//! end of the input a `go` still answers, while an `analyze` is stopped.
+use std::{
+    fmt::{Debug, Display},
+    io::{BufRead, Write},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use game::{
+    mcts::{Mcts, Playout},
+    Game, GameResult,
+};
+
+use crate::play::{parse_param, replay};
+
+/// Iterations between checks of the clock and of `stop`.
+const ITERATIONS_PER_CHECK: u32 = 256;
+
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+fn send(output: &Output, line: &str) {
+    let mut output = output.lock().unwrap();
+    // The other end hanging up ends the session on the next read anyway.
+    let _ = writeln!(output, "{}", line);
+    let _ = output.flush();
+}
+
+struct Search {
+    limited: bool,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+pub struct Engine<G, P> {
+    start: G,
+    position: G,
+    parse_position: fn(&str) -> Result<G, String>,
+    playout: fn(u64) -> P,
+    time: Duration,
+    seed: Option<u64>,
+    c: f64,
+    info: Duration,
+    output: Output,
+    search: Option<Search>,
+}
+
+impl<G, P> Engine<G, P>
+where
+    G: Game + Send + 'static,
+    G::Move: FromStr + Display + Send,
+    <G::Move as FromStr>::Err: Debug,
+    P: Playout<G> + Send + 'static,
+{
+    /// An engine at `start`, reading `position json` by `parse_position` and
+    /// playing out by `playout(seed)`.
+    pub fn new(
+        start: G,
+        parse_position: fn(&str) -> Result<G, String>,
+        playout: fn(u64) -> P,
+        c: f64,
+        output: Output,
+    ) -> Engine<G, P> {
+        Engine {
+            position: start.clone(),
+            start,
+            parse_position,
+            playout,
+            time: Duration::from_secs(1),
+            seed: None,
+            c,
+            info: Duration::from_millis(500),
+            output,
+            search: None,
+        }
+    }
+
+    /// Runs one command, returning `false` after `quit`.
+    pub fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        match command {
+            "" => {}
+            "isready" => send(&self.output, "readyok"),
+            "position" => {
+                let position = self.parse(args)?;
+                self.stop();
+                self.position = position;
+            }
+            "setoption" => self.set_option(args)?,
+            "go" => {
+                let time = match args.split_whitespace().collect::<Vec<_>>()[..] {
+                    [] => self.time,
+                    ["time", ms] => Duration::from_millis(parse_param("time", ms)?),
+                    _ => return Err(format!("unknown arguments of go: {}", args)),
+                };
+                self.go(Some(time));
+            }
+            "analyze" => self.go(None),
+            "stop" => self.stop(),
+            "quit" => {
+                self.stop();
+                return Ok(false);
+            }
+            _ => return Err(format!("unknown command: {}", command)),
+        }
+        Ok(true)
+    }
+
+    fn parse(&self, args: &str) -> Result<G, String> {
+        let (mut game, rest) = if let Some(rest) = args.strip_prefix("startpos") {
+            (self.start.clone(), rest)
+        } else if let Some(rest) = args.strip_prefix("json") {
+            // The board ends where the first JSON value does.
+            let mut values =
+                serde_json::Deserializer::from_str(rest).into_iter::<serde::de::IgnoredAny>();
+            values
+                .next()
+                .ok_or("missing board")?
+                .map_err(|e| e.to_string())?;
+            let end = values.byte_offset();
+            ((self.parse_position)(&rest[..end])?, &rest[end..])
+        } else {
+            return Err("position needs startpos or json".to_owned());
+        };
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            let moves = rest
+                .strip_prefix("moves")
+                .ok_or_else(|| format!("unexpected {}", rest))?
+                .split_whitespace()
+                .map(|mv| {
+                    mv.parse()
+                        .map_err(|e| format!("invalid move {}: {:?}", mv, e))
+                })
+                .collect::<Result<Vec<G::Move>, String>>()?;
+            replay(&mut game, &moves)?;
+        }
+        Ok(game)
+    }
+
+    fn set_option(&mut self, args: &str) -> Result<(), String> {
+        let (name, value) = args
+            .split_once(' ')
+            .ok_or("setoption needs a name and a value")?;
+        let value = value.trim();
+        match name {
+            "time" => self.time = Duration::from_millis(parse_param("time", value)?),
+            "seed" if value == "random" => self.seed = None,
+            "seed" => self.seed = Some(parse_param("seed", value)?),
+            "c" => self.c = parse_param("c", value)?,
+            "info" => self.info = Duration::from_millis(parse_param("info", value)?),
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// Starts searching the position in the background, for `limit` or until
+    /// `stop`.
+    fn go(&mut self, limit: Option<Duration>) {
+        self.stop();
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mcts = Mcts::new(&self.position, (self.playout)(seed), self.c);
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = stop.clone();
+            let output = self.output.clone();
+            let interval = self.info;
+            thread::spawn(move || search(mcts, limit, interval, &stop, &output))
+        };
+        self.search = Some(Search {
+            limited: limit.is_some(),
+            stop,
+            handle,
+        });
+    }
+
+    /// Ends the search, if any, once it has answered.
+    pub fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
+            search.handle.join().unwrap();
+        }
+    }
+
+    /// Waits for the search, if any, to end by itself.
+    pub fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.handle.join().unwrap();
+        }
+    }
+
+    /// Lets a `go` answer and stops an `analyze`.
+    fn finish(&mut self) {
+        match &self.search {
+            Some(search) if search.limited => self.wait(),
+            _ => self.stop(),
+        }
+    }
+}
+
+fn info<G: Game, P: Playout<G>>(mcts: &Mcts<G, P>, time: Duration) -> String
+where
+    G::Move: Display,
+{
+    let mut line = format!(
+        "info time {} visits {} nodes {}",
+        time.as_millis(),
+        mcts.visits(),
+        mcts.node_count()
+    );
+    if let Some(best) = mcts.best() {
+        line += &format!(" best {} point {:.3}", best.mv, best.point);
+    }
+    if let Some(proof) = mcts.proof() {
+        let result = match proof.result {
+            GameResult::Draw => "draw",
+            GameResult::Win(player) if player == mcts.root().to_move() => "win",
+            GameResult::Win(_) => "loss",
+        };
+        line += &format!(" proof {} {}", result, proof.plies);
+    }
+    line
+}
+
+fn search<G: Game, P: Playout<G>>(
+    mut mcts: Mcts<G, P>,
+    limit: Option<Duration>,
+    interval: Duration,
+    stop: &AtomicBool,
+    output: &Output,
+) where
+    G::Move: Display,
+{
+    let start = Instant::now();
+    let mut last = start;
+    while !stop.load(Ordering::Relaxed)
+        && mcts.proof().is_none()
+        && limit.is_none_or(|limit| start.elapsed() < limit)
+    {
+        mcts.run(ITERATIONS_PER_CHECK);
+        if last.elapsed() >= interval {
+            send(output, &info(&mcts, start.elapsed()));
+            last = Instant::now();
+        }
+    }
+    send(output, &info(&mcts, start.elapsed()));
+    // Stopped before the first expansion, any legal move will do.
+    let best = mcts
+        .best()
+        .map(|best| best.mv)
+        .or_else(|| mcts.root().legal_moves().first().copied());
+    match best {
+        Some(mv) => send(output, &format!("bestmove {}", mv)),
+        None => send(output, "bestmove none"),
+    }
+}
+
+/// Runs commands from `input` until `quit` or the end of the input.
+pub fn run<G, P>(engine: &mut Engine<G, P>, input: &mut impl BufRead) -> Result<(), String>
+where
+    G: Game + Send + 'static,
+    G::Move: FromStr + Display + Send,
+    <G::Move as FromStr>::Err: Debug,
+    P: Playout<G> + Send + 'static,
+{
+    for line in input.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        match engine.execute(&line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => send(&engine.output, &format!("error {}", e)),
+        }
+    }
+    engine.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use connect_four::BitBoard;
+    use game::mcts::RandomPlayout;
+
+    use super::*;
+    use crate::connect_four::parse_position;
+
+    fn engine() -> (Engine<BitBoard, RandomPlayout>, Arc<Mutex<Vec<u8>>>) {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let engine = Engine::new(
+            BitBoard::new(),
+            parse_position,
+            RandomPlayout::new,
+            2.0,
+            buffer.clone(),
+        );
+        (engine, buffer)
+    }
+
+    fn lines(buffer: &Mutex<Vec<u8>>) -> Vec<String> {
+        let text = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        text.lines().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn go() {
+        let (mut engine, buffer) = engine();
+        engine.execute("setoption seed 1").unwrap();
+        engine
+            .execute("position startpos moves 0 1 0 1 0 1")
+            .unwrap();
+        engine.execute("go time 5000").unwrap();
+        engine.wait();
+        let lines = lines(&buffer);
+        assert!(lines[0].starts_with("info time "));
+        assert!(lines[0].ends_with(" best 0 point 1.000 proof win 1"));
+        assert_eq!(lines[1], "bestmove 0");
+    }
+
+    #[test]
+    fn analyze_until_stop() {
+        let (mut engine, buffer) = engine();
+        for command in ["setoption info 10", "setoption c 1.4", "analyze"] {
+            engine.execute(command).unwrap();
+        }
+        thread::sleep(Duration::from_millis(100));
+        engine.execute("stop").unwrap();
+        let lines = lines(&buffer);
+        assert!(lines.len() > 2);
+        assert!(lines.iter().rev().skip(1).all(|l| l.starts_with("info ")));
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn commands() {
+        let (mut engine, buffer) = engine();
+        let json = r#"{"cols": [["A", "A", "A"], ["B", "B", "B"], [], [], [], [], []]}"#;
+        let mut input = Cursor::new(format!(
+            "isready\nfly\nposition startpos moves 0 0 0 0 0 0 0\nsetoption depth 3\n\
+             setoption time 100\nposition json {} moves 0\ngo\nquit\nisready\n",
+            json
+        ));
+        run(&mut engine, &mut input).unwrap();
+        let lines = lines(&buffer);
+        assert_eq!(lines[0], "readyok");
+        assert_eq!(lines[1], "error unknown command: fly");
+        assert_eq!(lines[2], "error illegal move: 0");
+        assert_eq!(lines[3], "error unknown option: depth");
+        // A connects four in the first column.
+        assert!(lines[4].starts_with("info "));
+        assert_eq!(lines[5], "bestmove none");
+        assert_eq!(lines.len(), 6);
+        assert!(engine.execute("position json {").is_err());
+        assert!(engine.execute("position startpos 3").is_err());
+        assert!(engine.execute("go time x").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! Plays the games of this workspace in the terminal against their AIs, for
+//! debugging without the web stack, runs tournaments between the AIs and mines
+//! puzzles.
+mod connect_four;
+mod engine;
+mod mancala;
+mod play;
+mod puzzles;
+mod rating;
+mod tic_tac_toe;
+mod tournament;
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use clap::{Parser, Subcommand};
+use game::{
+    mcts::{self, RandomPlayout},
+    Game, GameResult,
+};
+
+use crate::{
+    engine::Engine,
+    play::{Ai, Table},
+};
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    game: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Tic-tac-toe on boards up to 8x8, X first.
+    TicTacToe(tic_tac_toe::Options),
+    /// Connect four, A first.
+    ConnectFour(connect_four::Options),
+    /// Kalah with any number of pits and seeds.
+    Kalah(mancala::KalahOptions),
+    /// Oware (Abapa).
+    Oware(mancala::OwareOptions),
+    /// Serves an MCTS engine over a line protocol on stdin and stdout.
+    Engine {
+        #[command(subcommand)]
+        game: EngineGame,
+    },
+    /// Matches between AIs of one game.
+    Tournament {
+        #[command(subcommand)]
+        game: Tournament,
+    },
+    /// Mines "win in N" puzzles from random self-play as JSON.
+    Puzzles {
+        #[command(subcommand)]
+        game: Puzzles,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum EngineGame {
+    ConnectFour {
+        #[command(flatten)]
+        variant: connect_four::Variant,
+    },
+    Kalah {
+        #[command(flatten)]
+        variant: mancala::KalahVariant,
+    },
+    Oware {
+        #[command(flatten)]
+        variant: mancala::OwareVariant,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Tournament {
+    TicTacToe {
+        #[command(flatten)]
+        variant: tic_tac_toe::Variant,
+        #[command(flatten)]
+        settings: tournament::Settings,
+    },
+    ConnectFour {
+        #[command(flatten)]
+        variant: connect_four::Variant,
+        #[command(flatten)]
+        settings: tournament::Settings,
+    },
+    Kalah {
+        #[command(flatten)]
+        variant: mancala::KalahVariant,
+        #[command(flatten)]
+        settings: tournament::Settings,
+    },
+    Oware {
+        #[command(flatten)]
+        variant: mancala::OwareVariant,
+        #[command(flatten)]
+        settings: tournament::Settings,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum Puzzles {
+    TicTacToe {
+        #[command(flatten)]
+        variant: tic_tac_toe::Variant,
+        #[command(flatten)]
+        settings: puzzles::Settings,
+    },
+    ConnectFour {
+        #[command(flatten)]
+        variant: connect_four::Variant,
+        #[command(flatten)]
+        settings: puzzles::Settings,
+    },
+    Kalah {
+        #[command(flatten)]
+        variant: mancala::KalahVariant,
+        #[command(flatten)]
+        settings: puzzles::Settings,
+    },
+    Oware {
+        #[command(flatten)]
+        variant: mancala::OwareVariant,
+        #[command(flatten)]
+        settings: puzzles::Settings,
+    },
+}
+
+fn run<G>(table: Result<Table<G>, String>) -> Result<(), String>
+where
+    G: Game,
+    G::Move: std::str::FromStr,
+    <G::Move as std::str::FromStr>::Err: std::fmt::Debug,
+{
+    table?.play(&mut io::stdin().lock(), &mut io::stdout().lock())?;
+    Ok(())
+}
+
+fn run_tournament<G, F, A>(
+    start: Result<G, String>,
+    settings: &tournament::Settings,
+    build_ai: F,
+    adjudicate: A,
+) -> Result<(), String>
+where
+    G: Game,
+    F: Fn(&str, Option<u64>) -> Result<Ai<G>, String>,
+    A: Fn(&G) -> GameResult,
+{
+    tournament::run(
+        &start?,
+        settings,
+        build_ai,
+        adjudicate,
+        &mut io::stdout().lock(),
+    )?;
+    Ok(())
+}
+
+fn mine<G, V>(
+    name: &str,
+    variant: Result<V, String>,
+    start: Result<G, String>,
+    opening: &[G::Move],
+    settings: &puzzles::Settings,
+) -> Result<(), String>
+where
+    G: Game,
+    G::Move: serde::Serialize,
+    V: serde::Serialize,
+{
+    puzzles::run(
+        name,
+        variant?,
+        &start?,
+        opening,
+        settings,
+        &mut io::stdout().lock(),
+    )?;
+    Ok(())
+}
+
+fn serve<G, P>(
+    start: Result<G, String>,
+    parse_position: fn(&str) -> Result<G, String>,
+    playout: fn(u64) -> P,
+    c: f64,
+) -> Result<(), String>
+where
+    G: Game + Send + 'static,
+    G::Move: std::str::FromStr + std::fmt::Display + Send,
+    <G::Move as std::str::FromStr>::Err: std::fmt::Debug,
+    P: mcts::Playout<G> + Send + 'static,
+{
+    let output = Arc::new(Mutex::new(io::stdout()));
+    let mut engine = Engine::new(start?, parse_position, playout, c, output);
+    engine::run(&mut engine, &mut io::stdin().lock())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let r = match &cli.game {
+        Command::TicTacToe(options) => run(tic_tac_toe::table(options)),
+        Command::ConnectFour(options) => run(connect_four::table(options)),
+        Command::Kalah(options) => run(mancala::kalah_table(options)),
+        Command::Oware(options) => run(mancala::oware_table(options)),
+        Command::Engine { game } => match game {
+            EngineGame::ConnectFour { variant } => serve(
+                variant.start(),
+                connect_four::parse_position,
+                connect_four::playout,
+                2.0,
+            ),
+            EngineGame::Kalah { variant } => serve(
+                variant.start(),
+                mancala::parse_kalah,
+                RandomPlayout::new,
+                1.4,
+            ),
+            EngineGame::Oware { variant } => serve(
+                variant.start(),
+                mancala::parse_oware,
+                RandomPlayout::new,
+                1.4,
+            ),
+        },
+        Command::Tournament { game } => match game {
+            Tournament::TicTacToe { variant, settings } => {
+                run_tournament(variant.start(), settings, tic_tac_toe::build_ai, |_| {
+                    GameResult::Draw
+                })
+            }
+            Tournament::ConnectFour { variant, settings } => {
+                run_tournament(variant.start(), settings, connect_four::build_ai, |_| {
+                    GameResult::Draw
+                })
+            }
+            Tournament::Kalah { variant, settings } => run_tournament(
+                variant.start(),
+                settings,
+                mancala::build_kalah_ai,
+                |board| mancala::adjudicate(board.last_scores()),
+            ),
+            Tournament::Oware { variant, settings } => run_tournament(
+                variant.start(),
+                settings,
+                mancala::build_oware_ai,
+                |board| mancala::adjudicate(board.last_scores()),
+            ),
+        },
+        Command::Puzzles { game } => match game {
+            Puzzles::TicTacToe { variant, settings } => mine(
+                ::tic_tac_toe::RECORD_GAME,
+                variant.rules(),
+                variant.start(),
+                &variant.moves,
+                settings,
+            ),
+            Puzzles::ConnectFour { variant, settings } => mine(
+                ::connect_four::RECORD_GAME,
+                variant.rules(),
+                variant.start(),
+                &variant.moves,
+                settings,
+            ),
+            Puzzles::Kalah { variant, settings } => mine(
+                "kalah",
+                variant.rules(),
+                variant.start(),
+                &variant.moves,
+                settings,
+            ),
+            Puzzles::Oware { variant, settings } => mine(
+                "oware",
+                variant.rules(),
+                variant.start(),
+                &variant.moves,
+                settings,
+            ),
+        },
+    };
+    if let Err(e) = r {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
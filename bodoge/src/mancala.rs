@@ -0,0 +1,299 @@
+use std::cmp::Ordering;
+
+use clap::Args;
+use game::{GameResult, Player};
+use mancala::{is_mancala_rust_searcher, kalah, oware, search_turn};
+
+use crate::play::{replay, Ai, Common, Table};
+
+/// The board and the opening of Kalah.
+#[derive(Debug, Clone, Args)]
+pub struct KalahVariant {
+    #[arg(long, default_value_t = 6)]
+    pub pits: usize,
+    /// Seeds in each pit at the start.
+    #[arg(long, default_value_t = 4)]
+    pub seeds: u8,
+    /// Play without capturing the opposite pit.
+    #[arg(long)]
+    pub no_stealing: bool,
+    /// A board as the JSON object of the web UI, played on from there instead of
+    /// the start.
+    #[arg(long)]
+    pub position: Option<String>,
+    /// Pits sown before the game begins, separated by commas.
+    #[arg(long, value_delimiter = ',')]
+    pub moves: Vec<usize>,
+}
+
+/// Reads a Kalah board as the JSON object of the web UI.
+pub fn parse_kalah(json: &str) -> Result<kalah::Board, String> {
+    let board: kalah::Board = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    board.validate()?;
+    Ok(board)
+}
+
+impl KalahVariant {
+    pub fn start(&self) -> Result<kalah::Board, String> {
+        let mut board = match &self.position {
+            Some(position) => parse_kalah(position)?,
+            None => kalah::Board::new(self.pits, self.seeds, !self.no_stealing)?,
+        };
+        replay(&mut board, &self.moves)?;
+        Ok(board)
+    }
+
+    /// The rules of a game from the start.
+    pub fn rules(&self) -> Result<mancala::KalahVariant, String> {
+        if self.position.is_some() {
+            return Err("--position has no record of how it was reached".to_owned());
+        }
+        Ok(mancala::KalahVariant {
+            pits: self.pits,
+            seeds: self.seeds,
+            stealing: !self.no_stealing,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct KalahOptions {
+    #[command(flatten)]
+    pub variant: KalahVariant,
+    #[command(flatten)]
+    pub common: Common,
+    /// A searcher of `mancala_rust` for the standard 6-pit, 4-seed board, or
+    /// `random`, `dfs:<depth>` or `mcts:<limit ms>:<c>[:<seed>]` on any board.
+    #[arg(long, default_value = "mcts:1000:1.4")]
+    pub ai: String,
+}
+
+/// The opening of Oware.
+#[derive(Debug, Clone, Args)]
+pub struct OwareVariant {
+    /// A board as the JSON object of the web UI, played on from there instead of
+    /// the start.
+    #[arg(long)]
+    pub position: Option<String>,
+    /// Pits sown before the game begins, separated by commas.
+    #[arg(long, value_delimiter = ',')]
+    pub moves: Vec<usize>,
+}
+
+/// Reads an Oware board as the JSON object of the web UI.
+pub fn parse_oware(json: &str) -> Result<oware::Board, String> {
+    let board: oware::Board = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    board.validate()?;
+    Ok(board)
+}
+
+/// The result of a game stopped before its end, each side keeping the seeds in
+/// its row as when the game ends.
+pub fn adjudicate((first, second): (u8, u8)) -> GameResult {
+    match first.cmp(&second) {
+        Ordering::Greater => GameResult::Win(Player::First),
+        Ordering::Less => GameResult::Win(Player::Second),
+        Ordering::Equal => GameResult::Draw,
+    }
+}
+
+impl OwareVariant {
+    pub fn start(&self) -> Result<oware::Board, String> {
+        let mut board = match &self.position {
+            Some(position) => parse_oware(position)?,
+            None => oware::Board::new(),
+        };
+        replay(&mut board, &self.moves)?;
+        Ok(board)
+    }
+
+    /// The rules of a game from the start.
+    pub fn rules(&self) -> Result<mancala::OwareVariant, String> {
+        if self.position.is_some() {
+            return Err("--position has no record of how it was reached".to_owned());
+        }
+        Ok(mancala::OwareVariant {})
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct OwareOptions {
+    #[command(flatten)]
+    pub variant: OwareVariant,
+    #[command(flatten)]
+    pub common: Common,
+    /// `random` or `dfs:<depth>`.
+    #[arg(long, default_value = "dfs:6")]
+    pub ai: String,
+}
+
+/// The second player's row right to left above the first player's row, with the
+/// stores or captured seeds on both ends and the pit numbers of each row on its
+/// outer side.
+fn render_rows(seeds: [&[u8]; 2], score: [u8; 2], to_move: usize) -> String {
+    let row = |seeds: &mut dyn Iterator<Item = &u8>| {
+        seeds
+            .map(|s| format!("{:>3}", s))
+            .collect::<Vec<_>>()
+            .join("")
+    };
+    let pits = seeds[0].len();
+    let marks = if to_move == 0 {
+        ["  ", "> "]
+    } else {
+        ["> ", "  "]
+    };
+    let numbers = |numbers: &mut dyn Iterator<Item = usize>| {
+        numbers.map(|i| format!("{:>3}", i)).collect::<String>()
+    };
+    [
+        format!("     {}", numbers(&mut (0..pits).rev())),
+        format!("{}   {}", marks[0], row(&mut seeds[1].iter().rev())),
+        format!("  {:>3}{}{:>3}", score[1], " ".repeat(pits * 3), score[0]),
+        format!("{}   {}", marks[1], row(&mut seeds[0].iter())),
+        format!("     {}", numbers(&mut (0..pits))),
+    ]
+    .join("\n")
+}
+
+pub fn render_kalah(board: &kalah::Board) -> String {
+    let [first, second] = board.seeds();
+    render_rows([first, second], board.score(), board.side().index())
+}
+
+pub fn render_oware(board: &oware::Board) -> String {
+    let [first, second] = board.seeds();
+    render_rows([first, second], board.score(), board.side().index())
+}
+
+/// With a seed, the searchers of [`kalah`] draw from it; the others and every
+/// searcher without a seed go through [`search_turn`]. A spec that neither
+/// [`kalah`] nor `mancala_rust` knows is rejected here, before any game.
+pub fn build_kalah_ai(spec: &str, seed: Option<u64>) -> Result<Ai<kalah::Board>, String> {
+    let ai = match seed {
+        Some(seed) => kalah::build_seeded_ai(spec, seed),
+        None => kalah::build_ai(spec),
+    };
+    match ai {
+        Ok(mut ai) if seed.is_some() => Ok(Box::new(move |board: &kalah::Board| Ok(ai.sow(board)))),
+        Err(e) if !is_mancala_rust_searcher(spec) => Err(e),
+        _ => {
+            let spec = spec.to_owned();
+            Ok(Box::new(move |board: &kalah::Board| {
+                search_turn(board, &spec)
+            }))
+        }
+    }
+}
+
+pub fn build_oware_ai(spec: &str, seed: Option<u64>) -> Result<Ai<oware::Board>, String> {
+    let mut ai = match seed {
+        Some(seed) => oware::build_seeded_ai(spec, seed)?,
+        None => oware::build_ai(spec)?,
+    };
+    Ok(Box::new(move |board: &oware::Board| {
+        Ok(vec![ai.sow(board)])
+    }))
+}
+
+pub fn kalah_table(options: &KalahOptions) -> Result<Table<kalah::Board>, String> {
+    Ok(Table {
+        game: options.variant.start()?,
+        render: render_kalah,
+        names: ["First", "Second"],
+        human: options.common.human,
+        ai: build_kalah_ai(&options.ai, options.common.seed)?,
+    })
+}
+
+pub fn oware_table(options: &OwareOptions) -> Result<Table<oware::Board>, String> {
+    Ok(Table {
+        game: options.variant.start()?,
+        render: render_oware,
+        names: ["First", "Second"],
+        human: options.common.human,
+        ai: build_oware_ai(&options.ai, options.common.seed)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::{play::Human, tournament::play_game};
+
+    #[test]
+    fn render_board() {
+        let mut board = kalah::Board::new(4, 3, true).unwrap();
+        board.sow(0);
+        assert_eq!(
+            render_kalah(&board),
+            [
+                "       3  2  1  0",
+                ">      3  3  3  3",
+                "    0              0",
+                "       0  4  4  4",
+                "       0  1  2  3",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn kalah_specs() {
+        for seed in [None, Some(1)] {
+            assert!(build_kalah_ai("dfs:2", seed).is_ok());
+            assert!(build_kalah_ai("dfs:nn6:3", seed).is_ok());
+            assert!(build_kalah_ai("dfs:0", seed).is_err());
+            assert!(build_kalah_ai("nope", seed).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_oware_checks() {
+        assert!(parse_oware(
+            r#"{"seeds": [[200, 0, 0, 0, 0, 0], [0, 0, 0, 0, 0, 1]], "score": [0, 0], "side": "First"}"#
+        )
+        .is_err());
+        assert!(parse_oware(
+            r#"{"seeds": [[4, 4, 4, 4, 4, 4], [4, 4, 4, 4, 4, 4]], "score": [0, 0], "side": "First"}"#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn adjudication() {
+        let mut ais = [
+            build_oware_ai("random", Some(1)).unwrap(),
+            build_oware_ai("random", Some(2)).unwrap(),
+        ];
+        let called = Cell::new(false);
+        let adjudicated = |board: &oware::Board| {
+            assert!(!board.is_finished());
+            called.set(true);
+            adjudicate(board.last_scores())
+        };
+        play_game(&oware::Board::new(), &mut ais, 6, adjudicated).unwrap();
+        assert!(called.get());
+        assert_eq!(adjudicate((30, 18)), GameResult::Win(Player::First));
+    }
+
+    #[test]
+    fn oware_cycle_ends() {
+        // Two lone seeds chase each other around the board forever, until the
+        // rule of oware::MAX_QUIET_PLIES ends the game.
+        let mut table = Table {
+            game: parse_oware(
+                r#"{"seeds": [[0, 0, 0, 0, 0, 1], [0, 0, 0, 0, 0, 1]], "score": [23, 23], "side": "First"}"#,
+            )
+            .unwrap(),
+            render: render_oware,
+            names: ["First", "Second"],
+            human: Human::None,
+            ai: build_oware_ai("random", Some(1)).unwrap(),
+        };
+        let result = table.play(&mut "".as_bytes(), &mut Vec::new()).unwrap();
+        assert_eq!(result, GameResult::Draw);
+    }
+}
@@ -0,0 +1,203 @@
+//! The loop shared by every game: show the position, ask the human or the AI for
+//! a move, and repeat until the game is over.
+use std::{
+    fmt::{Debug, Display},
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use clap::{Args, ValueEnum};
+use game::{Game, GameResult, Player};
+
+/// Who types in moves.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, ValueEnum)]
+pub enum Human {
+    First,
+    Second,
+    Both,
+    None,
+}
+
+impl Human {
+    fn plays(self, player: Player) -> bool {
+        match self {
+            Human::First => player == Player::First,
+            Human::Second => player == Player::Second,
+            Human::Both => true,
+            Human::None => false,
+        }
+    }
+}
+
+/// Options of playing every game.
+#[derive(Debug, Clone, Args)]
+pub struct Common {
+    /// Who types in moves; the AI plays the others.
+    #[arg(long, value_enum, default_value_t = Human::First)]
+    pub human: Human,
+    /// Seed of the AI, random by default. The `mancala_rust` searchers of Kalah
+    /// are never seeded.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// One full turn of the AI for the player to move.
+pub type Ai<G> = Box<dyn FnMut(&G) -> Result<Vec<<G as Game>::Move>, String>>;
+
+/// Plays `moves` on `game`, checking that each is legal.
+pub fn replay<G: Game>(game: &mut G, moves: &[G::Move]) -> Result<(), String> {
+    for &mv in moves {
+        if !game.legal_moves().contains(&mv) {
+            return Err(format!("illegal move: {:?}", mv));
+        }
+        game.apply(mv);
+    }
+    Ok(())
+}
+
+/// A game in progress with how to show it and who plays it.
+pub struct Table<G: Game> {
+    pub game: G,
+    pub render: fn(&G) -> String,
+    pub names: [&'static str; 2],
+    pub human: Human,
+    pub ai: Ai<G>,
+}
+
+impl<G> Table<G>
+where
+    G: Game,
+    G::Move: FromStr,
+    <G::Move as FromStr>::Err: Debug,
+{
+    /// Plays to the end, reading the moves of humans from `input` one per line.
+    pub fn play<I: BufRead, O: Write>(
+        &mut self,
+        input: &mut I,
+        output: &mut O,
+    ) -> Result<GameResult, String> {
+        let io = |e: std::io::Error| e.to_string();
+        loop {
+            writeln!(output, "{}", (self.render)(&self.game)).map_err(io)?;
+            if let Some(result) = self.game.result() {
+                let message = match result {
+                    GameResult::Win(p) => format!("{} wins", self.names[p.index()]),
+                    GameResult::Draw => "draw".to_owned(),
+                };
+                writeln!(output, "{}", message).map_err(io)?;
+                return Ok(result);
+            }
+            let player = self.game.to_move();
+            let name = self.names[player.index()];
+            if !self.human.plays(player) {
+                let moves = (self.ai)(&self.game)?;
+                if moves.is_empty() {
+                    return Err(format!("{} made no move", name));
+                }
+                for mv in moves {
+                    if !self.game.legal_moves().contains(&mv) {
+                        return Err(format!("{} made an illegal move: {:?}", name, mv));
+                    }
+                    writeln!(output, "{} plays {:?}", name, mv).map_err(io)?;
+                    self.game.apply(mv);
+                }
+                continue;
+            }
+            loop {
+                write!(output, "{}> ", name).map_err(io)?;
+                output.flush().map_err(io)?;
+                let mut line = String::new();
+                if input.read_line(&mut line).map_err(io)? == 0 {
+                    return Err("input closed".to_owned());
+                }
+                match line.trim().parse::<G::Move>() {
+                    Ok(mv) if self.game.legal_moves().contains(&mv) => {
+                        self.game.apply(mv);
+                        break;
+                    }
+                    Ok(mv) => writeln!(output, "illegal move: {:?}", mv).map_err(io)?,
+                    Err(e) => {
+                        writeln!(output, "invalid move {:?}: {:?}", line.trim(), e).map_err(io)?
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_param<T: FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid {} {:?}: {}", name, value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tic_tac_toe::{self, Options, Variant};
+
+    fn options(human: Human, ai: &str, moves: Vec<usize>) -> Options {
+        Options {
+            variant: Variant {
+                width: 3,
+                height: 3,
+                k: 3,
+                position: None,
+                moves,
+            },
+            common: Common {
+                human,
+                seed: Some(1),
+            },
+            ai: ai.to_owned(),
+        }
+    }
+
+    #[test]
+    fn humans() {
+        let mut table = tic_tac_toe::table(&options(Human::Both, "search", vec![0, 3])).unwrap();
+        let mut input = "x\n3\n1\n4\n2\n".as_bytes();
+        let mut output = Vec::new();
+        let result = table.play(&mut input, &mut output).unwrap();
+        assert_eq!(result, GameResult::Win(Player::First));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("invalid move \"x\""));
+        assert!(output.contains("illegal move: 3"));
+        assert!(output.ends_with("X X X\nO O 5\n6 7 8\nX wins\n"));
+
+        let mut table = tic_tac_toe::table(&options(Human::First, "search", vec![])).unwrap();
+        assert!(table.play(&mut "".as_bytes(), &mut Vec::new()).is_err());
+        assert!(tic_tac_toe::table(&options(Human::Both, "search", vec![0, 0])).is_err());
+    }
+
+    #[test]
+    fn ais() {
+        for ai in ["search", "mcts:200:1.4", "negamax:9:1000"] {
+            let mut table = tic_tac_toe::table(&options(Human::None, ai, vec![])).unwrap();
+            let result = table.play(&mut "".as_bytes(), &mut Vec::new()).unwrap();
+            if ai != "mcts:200:1.4" {
+                assert_eq!(result, GameResult::Draw, "{}", ai);
+            }
+        }
+        assert!(tic_tac_toe::table(&options(Human::None, "mcts:x:1", vec![])).is_err());
+        assert!(tic_tac_toe::table(&options(Human::None, "minimax", vec![])).is_err());
+    }
+
+    #[test]
+    fn broken_ai() {
+        let mut table = tic_tac_toe::table(&options(Human::None, "search", vec![])).unwrap();
+        table.ai = Box::new(|_| Ok(vec![]));
+        let err = table.play(&mut "".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err, "X made no move");
+
+        let mut table = tic_tac_toe::table(&options(Human::Second, "search", vec![4])).unwrap();
+        table.ai = Box::new(|_| Ok(vec![4]));
+        let err = table
+            .play(&mut "0\n".as_bytes(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err, "X made an illegal move: 4");
+    }
+}
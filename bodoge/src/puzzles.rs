@@ -0,0 +1,187 @@
+//! Puzzle sets of "win in N" positions mined from random self-play, with the
+//! moves from the start and the solution line of each.
+use std::{cmp::Reverse, fs, io::Write, path::PathBuf};
+
+use clap::Args;
+use game::{
+    puzzle::{self, PuzzleSet},
+    Game,
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Args)]
+pub struct Settings {
+    /// Random games to mine, each position of them tried once.
+    #[arg(long, default_value_t = 100)]
+    pub games: u32,
+    /// Fewest plies to the win, counting the moves of both players.
+    #[arg(long, default_value_t = 3)]
+    pub min_plies: u32,
+    /// Most plies to the win.
+    #[arg(long, default_value_t = 7)]
+    pub max_plies: u32,
+    #[arg(long, default_value_t = 1)]
+    pub seed: u64,
+    /// Keeps this many puzzles, the longest wins first.
+    #[arg(long)]
+    pub count: Option<usize>,
+    /// Writes the puzzle set here instead of to standard output.
+    #[arg(long)]
+    pub json: Option<PathBuf>,
+}
+
+/// Mines the puzzles of `start`, which is reached by `opening` from the start
+/// of `variant`.
+pub fn run<G, V>(
+    name: &str,
+    variant: V,
+    start: &G,
+    opening: &[G::Move],
+    settings: &Settings,
+    output: &mut dyn Write,
+) -> Result<PuzzleSet<V, G::Move>, String>
+where
+    G: Game,
+    G::Move: Serialize,
+    V: Serialize,
+{
+    if settings.min_plies > settings.max_plies {
+        return Err("--min-plies is above --max-plies".to_owned());
+    }
+    let mut puzzles = puzzle::mine(
+        start,
+        settings.games,
+        settings.min_plies,
+        settings.max_plies,
+        settings.seed,
+    );
+    // Stable, so puzzles of one length stay in the order they were found.
+    puzzles.sort_by_key(|p| Reverse(p.plies));
+    if let Some(count) = settings.count {
+        puzzles.truncate(count);
+    }
+    for puzzle in &mut puzzles {
+        puzzle.moves.splice(0..0, opening.iter().copied());
+    }
+    let set = PuzzleSet {
+        game: name.to_owned(),
+        variant,
+        puzzles,
+    };
+    let json = serde_json::to_string_pretty(&set).map_err(|e| e.to_string())?;
+    let io = |e: std::io::Error| e.to_string();
+    match &settings.json {
+        Some(path) => {
+            fs::write(path, json).map_err(io)?;
+            writeln!(output, "{} puzzles", set.puzzles.len()).map_err(io)?;
+        }
+        None => writeln!(output, "{}", json).map_err(io)?,
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connect_four::BitBoard;
+    use game::{puzzle::wins_within, GameResult, Player};
+    use mancala::kalah;
+    use tic_tac_toe::grid::Grid;
+
+    fn settings(games: u32, min_plies: u32, max_plies: u32) -> Settings {
+        Settings {
+            games,
+            min_plies,
+            max_plies,
+            seed: 1,
+            count: None,
+            json: None,
+        }
+    }
+
+    /// Checks every puzzle of `set`, mined from `start` after `opening`, against
+    /// its definition, and returns the solutions.
+    fn check<G: Game, V>(
+        set: &PuzzleSet<V, G::Move>,
+        start: &G,
+        opening: &[G::Move],
+        settings: &Settings,
+    ) -> Vec<Vec<(Player, G::Move)>> {
+        assert!(!set.puzzles.is_empty());
+        let mut solutions = Vec::new();
+        for puzzle in &set.puzzles {
+            assert_eq!(puzzle.moves[..opening.len()], *opening);
+            assert!((settings.min_plies..=settings.max_plies).contains(&puzzle.plies));
+            assert_eq!(puzzle.solution.len(), puzzle.plies as usize);
+            let mut game = start.clone();
+            for &mv in &puzzle.moves[opening.len()..] {
+                game.apply(mv);
+            }
+            assert_eq!(game.to_move(), puzzle.to_move);
+            // No other move wins, however slowly, within the limit.
+            for mv in game.legal_moves() {
+                let mut next = game.clone();
+                next.apply(mv);
+                let wins = wins_within(&next, puzzle.to_move, settings.max_plies - 1);
+                assert_eq!(wins, mv == puzzle.solution[0], "{:?}", puzzle);
+            }
+            let mut solution = Vec::new();
+            for &mv in &puzzle.solution {
+                assert!(game.legal_moves().contains(&mv));
+                solution.push((game.to_move(), mv));
+                game.apply(mv);
+            }
+            assert_eq!(game.result(), Some(GameResult::Win(puzzle.to_move)));
+            solutions.push(solution);
+        }
+        solutions
+    }
+
+    #[test]
+    fn connect_four() {
+        let settings = Settings {
+            count: Some(4),
+            ..settings(5, 3, 3)
+        };
+        let mut output = Vec::new();
+        let opening = [3, 3];
+        let mut start = BitBoard::new();
+        for &mv in &opening {
+            start.apply(mv);
+        }
+        let set = run(
+            connect_four::RECORD_GAME,
+            connect_four::Variant {},
+            &start,
+            &opening,
+            &settings,
+            &mut output,
+        )
+        .unwrap();
+        assert!(set.puzzles.len() <= 4);
+        check(&set, &start, &opening, &settings);
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["game"], "connect-four");
+    }
+
+    #[test]
+    fn tic_tac_toe() {
+        let settings = settings(20, 3, 5);
+        let start = Grid::new(4, 4, 3).unwrap();
+        let set = run("tic-tac-toe", (), &start, &[], &settings, &mut Vec::new()).unwrap();
+        let solutions = check(&set, &start, &[], &settings);
+        assert!(solutions.iter().any(|s| s.len() == 5));
+    }
+
+    #[test]
+    fn kalah() {
+        let settings = settings(20, 2, 5);
+        let start = kalah::Board::new(4, 3, true).unwrap();
+        let set = run("kalah", (), &start, &[], &settings, &mut Vec::new()).unwrap();
+        let solutions = check(&set, &start, &[], &settings);
+        // Lines with extra turns, where one player moves twice in a row.
+        assert!(solutions
+            .iter()
+            .any(|s| s.windows(2).any(|w| w[0].0 == w[1].0)));
+    }
+}
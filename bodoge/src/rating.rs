@@ -0,0 +1,191 @@
+//! Elo estimates and the sequential probability ratio test over the results of a
+//! match.
+use serde::Serialize;
+
+/// Games won, drawn and lost by one side of a match.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// An Elo difference with its 95% confidence interval. Bounds are infinite when
+/// the score reaches 0 or 1.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+pub struct Elo {
+    pub estimate: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Outcome of the sequential probability ratio test.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+pub enum Sprt {
+    /// The difference is at most `elo0`.
+    H0,
+    /// The difference is at least `elo1`.
+    H1,
+}
+
+/// Elo difference expected to score `score` on average.
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Average score of a player `elo` stronger than its opponent.
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Least variance of a score in [`Record::llr`], which a record of one outcome
+/// falls below.
+const MIN_VARIANCE: f64 = 0.05;
+
+/// Lower and upper bounds of the log-likelihood ratio for false positive rate
+/// `alpha` and false negative rate `beta`.
+pub fn sprt_bounds(alpha: f64, beta: f64) -> (f64, f64) {
+    ((beta / (1.0 - alpha)).ln(), ((1.0 - beta) / alpha).ln())
+}
+
+impl Record {
+    pub fn add(&mut self, score: i32) {
+        match score {
+            1 => self.wins += 1,
+            0 => self.draws += 1,
+            _ => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The record of the other side.
+    pub fn flip(&self) -> Record {
+        Record {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+
+    /// Points per game, a draw counting as half a win.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    fn variance(&self) -> f64 {
+        let s = self.score();
+        let n = self.games() as f64;
+        (self.wins as f64 * (1.0 - s).powi(2)
+            + self.draws as f64 * (0.5 - s).powi(2)
+            + self.losses as f64 * s.powi(2))
+            / n
+    }
+
+    /// `None` before the first game.
+    pub fn elo(&self) -> Option<Elo> {
+        if self.games() == 0 {
+            return None;
+        }
+        let s = self.score();
+        let margin = 1.96 * (self.variance() / self.games() as f64).sqrt();
+        let elo = |s: f64| elo_from_score(s.clamp(0.0, 1.0));
+        Some(Elo {
+            estimate: elo(s),
+            low: elo(s - margin),
+            high: elo(s + margin),
+        })
+    }
+
+    /// Log-likelihood ratio of a difference of `elo1` against one of `elo0`, by
+    /// the normal approximation of the average score. `None` before the first
+    /// game. The variance is at least [`MIN_VARIANCE`], so that a clean sweep,
+    /// which has none, still ends the test.
+    pub fn llr(&self, elo0: f64, elo1: f64) -> Option<f64> {
+        if self.games() == 0 {
+            return None;
+        }
+        let variance = self.variance().max(MIN_VARIANCE);
+        let (s0, s1) = (score_from_elo(elo0), score_from_elo(elo1));
+        let n = self.games() as f64;
+        Some(n * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance))
+    }
+
+    /// The decision of the test once the ratio leaves the bounds of
+    /// [`sprt_bounds`].
+    pub fn sprt(&self, elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Option<Sprt> {
+        let llr = self.llr(elo0, elo1)?;
+        let (lower, upper) = sprt_bounds(alpha, beta);
+        if llr <= lower {
+            Some(Sprt::H0)
+        } else if llr >= upper {
+            Some(Sprt::H1)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(wins: u32, draws: u32, losses: u32) -> Record {
+        Record {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    #[test]
+    fn elo() {
+        assert_eq!(elo_from_score(0.5), 0.0);
+        assert!((elo_from_score(0.75) - 190.85).abs() < 0.01);
+        assert!((score_from_elo(elo_from_score(0.3)) - 0.3).abs() < 1e-9);
+
+        assert_eq!(record(0, 0, 0).elo(), None);
+        let even = record(10, 20, 10).elo().unwrap();
+        assert_eq!(even.estimate, 0.0);
+        assert!(even.low < 0.0 && even.high > 0.0);
+        assert_eq!(even.low, -even.high);
+
+        let strong = record(30, 5, 5).elo().unwrap();
+        assert!(strong.low > 0.0 && strong.low < strong.estimate);
+        assert!(strong.estimate < strong.high);
+
+        let perfect = record(10, 0, 0).elo().unwrap();
+        assert_eq!(perfect.estimate, f64::INFINITY);
+        assert_eq!(record(10, 0, 0).flip(), record(0, 0, 10));
+    }
+
+    #[test]
+    fn sprt() {
+        let (lower, upper) = sprt_bounds(0.05, 0.05);
+        assert!((upper - 2.944).abs() < 0.001);
+        assert_eq!(lower, -upper);
+
+        assert_eq!(record(0, 0, 0).llr(0.0, 50.0), None);
+        // A sweep decides within a few games, and endless draws do too.
+        assert_eq!(record(3, 0, 0).sprt(0.0, 50.0, 0.05, 0.05), None);
+        assert_eq!(record(5, 0, 0).sprt(0.0, 50.0, 0.05, 0.05), Some(Sprt::H1));
+        assert_eq!(record(0, 0, 5).sprt(0.0, 50.0, 0.05, 0.05), Some(Sprt::H0));
+        assert_eq!(
+            record(0, 100, 0).sprt(0.0, 50.0, 0.05, 0.05),
+            Some(Sprt::H0)
+        );
+        assert!(record(30, 10, 20).llr(0.0, 50.0).unwrap() > 0.0);
+        assert!(record(20, 10, 30).llr(0.0, 50.0).unwrap() < 0.0);
+        assert_eq!(
+            record(60, 20, 20).sprt(0.0, 50.0, 0.05, 0.05),
+            Some(Sprt::H1)
+        );
+        assert_eq!(
+            record(20, 20, 60).sprt(0.0, 50.0, 0.05, 0.05),
+            Some(Sprt::H0)
+        );
+        assert_eq!(record(6, 2, 5).sprt(0.0, 50.0, 0.05, 0.05), None);
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use clap::Args;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use tic_tac_toe::{
+    grid::{self, Grid},
+    search, CellType,
+};
+
+use crate::play::{parse_param, replay, Ai, Common, Table};
+
+/// The board and the opening.
+#[derive(Debug, Clone, Args)]
+pub struct Variant {
+    #[arg(long, default_value_t = 3)]
+    pub width: usize,
+    #[arg(long, default_value_t = 3)]
+    pub height: usize,
+    /// Marks in a row to win.
+    #[arg(long, default_value_t = 3)]
+    pub k: usize,
+    /// A 3x3 board as a JSON array of `"E"`, `"X"` and `"O"`, played on from there
+    /// instead of the empty board.
+    #[arg(long)]
+    pub position: Option<String>,
+    /// Cells played before the game begins, separated by commas.
+    #[arg(long, value_delimiter = ',')]
+    pub moves: Vec<usize>,
+}
+
+impl Variant {
+    pub fn start(&self) -> Result<Grid, String> {
+        let mut grid = match &self.position {
+            Some(position) => {
+                let cells: [CellType; 9] =
+                    serde_json::from_str(position).map_err(|e| e.to_string())?;
+                let count = |mark| cells.iter().filter(|&&c| c == mark).count();
+                let next = if count(CellType::X) == count(CellType::O) {
+                    CellType::X
+                } else {
+                    CellType::O
+                };
+                Grid::from_cells(&cells, next)
+            }
+            None => Grid::new(self.width, self.height, self.k)?,
+        };
+        replay(&mut grid, &self.moves)?;
+        Ok(grid)
+    }
+
+    /// The rules of a game from the empty board.
+    pub fn rules(&self) -> Result<tic_tac_toe::Variant, String> {
+        if self.position.is_some() {
+            return Err("--position has no record of how it was reached".to_owned());
+        }
+        Ok(tic_tac_toe::Variant {
+            width: self.width,
+            height: self.height,
+            k: self.k,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Options {
+    #[command(flatten)]
+    pub variant: Variant,
+    #[command(flatten)]
+    pub common: Common,
+    /// `search` (perfect play on 3x3), `mcts:<iterations>:<c>` or
+    /// `negamax:<depth>:<limit ms>`.
+    #[arg(long, default_value = "search")]
+    pub ai: String,
+}
+
+/// Rows of marks with every empty cell showing the number to type for it.
+pub fn render(grid: &Grid) -> String {
+    let digits = (grid.cells().len() - 1).to_string().len();
+    let mut lines = Vec::new();
+    for (y, row) in grid.cells().chunks(grid.width()).enumerate() {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(x, &cell)| {
+                let text = match cell {
+                    CellType::E => (y * grid.width() + x).to_string(),
+                    CellType::X => "X".to_owned(),
+                    CellType::O => "O".to_owned(),
+                };
+                format!("{:>width$}", text, width = digits)
+            })
+            .collect::<Vec<_>>();
+        lines.push(cells.join(" "));
+    }
+    lines.join("\n")
+}
+
+pub fn build_ai(spec: &str, seed: Option<u64>) -> Result<Ai<Grid>, String> {
+    let mut rng = match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    };
+    let words = spec.split(':').collect::<Vec<_>>();
+    match words[..] {
+        ["search"] => Ok(Box::new(|grid: &Grid| {
+            let cells: [CellType; 9] = grid
+                .cells()
+                .try_into()
+                .ok()
+                .filter(|_| grid.k() == 3)
+                .ok_or("search only plays the 3x3 board")?;
+            let r = search(&cells, grid.next());
+            Ok(r.position.map(|pos| pos as usize).into_iter().collect())
+        })),
+        ["mcts", iterations, c] => {
+            let iterations = parse_param("iterations", iterations)?;
+            let c = parse_param("c", c)?;
+            Ok(Box::new(move |grid: &Grid| {
+                Ok(grid::mcts(grid, iterations, c, rng.gen())
+                    .map(|best| best.mv)
+                    .into_iter()
+                    .collect())
+            }))
+        }
+        ["negamax", depth, limit] => {
+            let depth = parse_param("depth", depth)?;
+            let limit = Duration::from_millis(parse_param("limit", limit)?);
+            Ok(Box::new(move |grid: &Grid| {
+                Ok(grid::alpha_beta(grid, depth, limit)
+                    .best
+                    .into_iter()
+                    .collect())
+            }))
+        }
+        _ => Err(format!("unknown ai: {}", spec)),
+    }
+}
+
+pub fn table(options: &Options) -> Result<Table<Grid>, String> {
+    Ok(Table {
+        game: options.variant.start()?,
+        render,
+        names: ["X", "O"],
+        human: options.common.human,
+        ai: build_ai(&options.ai, options.common.seed)?,
+    })
+}
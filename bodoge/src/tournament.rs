@@ -0,0 +1,424 @@
+//! Matches between AIs of one game: every pair of players, or the first player
+//! against each other one, with Elo estimates and an optional SPRT per pairing.
+use std::{fs, io::Write, path::PathBuf};
+
+use clap::Args;
+use game::{Game, GameResult, Player};
+use serde::Serialize;
+
+use crate::{
+    play::Ai,
+    rating::{Elo, Record, Sprt},
+};
+
+#[derive(Debug, Clone, Args)]
+pub struct Settings {
+    /// An AI spec as taken by `--ai` of the game; give at least two.
+    #[arg(long = "player", required = true)]
+    pub players: Vec<String>,
+    /// Games of each pairing, the players taking turns at moving first.
+    #[arg(long, default_value_t = 20)]
+    pub games: u32,
+    /// Match the first player against each other one instead of every pair.
+    #[arg(long)]
+    pub gauntlet: bool,
+    /// Plies after which a game that has not ended is adjudicated: a draw, or
+    /// the side holding more seeds in mancala.
+    #[arg(long, default_value_t = 1000)]
+    pub max_plies: u32,
+    /// Seed from which the seed of every AI in every game is derived.
+    #[arg(long, default_value_t = 1)]
+    pub seed: u64,
+    /// Stop a pairing once the SPRT decides whether the first player is at most
+    /// `<elo0>` or at least `<elo1>` stronger, as `<elo0>,<elo1>`.
+    #[arg(long, value_delimiter = ',', value_name = "ELO0,ELO1")]
+    pub sprt: Option<Vec<f64>>,
+    /// False positive rate of the SPRT.
+    #[arg(long, default_value_t = 0.05)]
+    pub alpha: f64,
+    /// False negative rate of the SPRT.
+    #[arg(long, default_value_t = 0.05)]
+    pub beta: f64,
+    /// Writes the pairings as CSV.
+    #[arg(long)]
+    pub csv: Option<PathBuf>,
+    /// Writes the pairings and standings as JSON.
+    #[arg(long)]
+    pub json: Option<PathBuf>,
+}
+
+/// The games between two players, counted for the first.
+#[derive(Debug, Clone, Serialize)]
+pub struct Pairing {
+    pub player: String,
+    pub opponent: String,
+    #[serde(flatten)]
+    pub record: Record,
+    pub elo: Option<Elo>,
+    pub llr: Option<f64>,
+    pub sprt: Option<Sprt>,
+}
+
+/// The games of one player against every opponent.
+#[derive(Debug, Clone, Serialize)]
+pub struct Standing {
+    pub player: String,
+    #[serde(flatten)]
+    pub record: Record,
+    pub score: f64,
+    pub elo: Option<Elo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub seed: u64,
+    pub pairings: Vec<Pairing>,
+    pub standings: Vec<Standing>,
+}
+
+/// SplitMix64 of `x`, to derive independent seeds from the settings.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn ai_seed(seed: u64, pairing: usize, game: u32, player: Player) -> u64 {
+    mix(mix(mix(seed) ^ pairing as u64) ^ game as u64) ^ player.index() as u64
+}
+
+/// Plays one game from `start` to the end, or to the first turn after
+/// `max_plies` moves, where `adjudicate` gives the result.
+pub fn play_game<G: Game>(
+    start: &G,
+    ais: &mut [Ai<G>; 2],
+    max_plies: u32,
+    adjudicate: impl Fn(&G) -> GameResult,
+) -> Result<GameResult, String> {
+    let mut game = start.clone();
+    let mut plies = 0;
+    loop {
+        if let Some(result) = game.result() {
+            return Ok(result);
+        }
+        if plies >= max_plies {
+            return Ok(adjudicate(&game));
+        }
+        let player = game.to_move();
+        let moves = ais[player.index()](&game)?;
+        if moves.is_empty() {
+            return Err(format!("no move from player {:?}", player));
+        }
+        for mv in moves {
+            if game.to_move() != player || !game.legal_moves().contains(&mv) {
+                return Err(format!("illegal move from player {:?}: {:?}", player, mv));
+            }
+            game.apply(mv);
+            plies += 1;
+        }
+    }
+}
+
+fn pairs(players: usize, gauntlet: bool) -> Vec<(usize, usize)> {
+    if gauntlet {
+        (1..players).map(|j| (0, j)).collect()
+    } else {
+        (0..players)
+            .flat_map(|i| (i + 1..players).map(move |j| (i, j)))
+            .collect()
+    }
+}
+
+fn format_elo(elo: Option<Elo>) -> String {
+    match elo {
+        Some(elo) => format!("{:+.0} [{:+.0}, {:+.0}]", elo.estimate, elo.low, elo.high),
+        None => "-".to_owned(),
+    }
+}
+
+/// Plays the tournament from `start`, building each AI by `build_ai(spec, seed)`
+/// and reporting every game to `output`. Games that reach the ply cap are
+/// decided by `adjudicate`.
+pub fn run<G, F, A>(
+    start: &G,
+    settings: &Settings,
+    build_ai: F,
+    adjudicate: A,
+    output: &mut impl Write,
+) -> Result<Report, String>
+where
+    G: Game,
+    F: Fn(&str, Option<u64>) -> Result<Ai<G>, String>,
+    A: Fn(&G) -> GameResult,
+{
+    let players = &settings.players;
+    if players.len() < 2 {
+        return Err("a tournament needs at least two players".to_owned());
+    }
+    let sprt = match settings.sprt.as_deref() {
+        Some(&[elo0, elo1]) if elo0 < elo1 => Some((elo0, elo1)),
+        Some(_) => return Err("sprt needs two differences, elo0 < elo1".to_owned()),
+        None => None,
+    };
+    // Rejects bad specs before the first game.
+    for spec in players {
+        let _ = build_ai(spec, Some(settings.seed))?;
+    }
+    let io = |e: std::io::Error| e.to_string();
+
+    let mut pairings = Vec::new();
+    let mut totals = vec![Record::default(); players.len()];
+    for (index, &(a, b)) in pairs(players.len(), settings.gauntlet).iter().enumerate() {
+        let mut record = Record::default();
+        let mut decision = None;
+        for game in 0..settings.games {
+            let a_first = game.is_multiple_of(2);
+            let order = if a_first { [a, b] } else { [b, a] };
+            let mut ais = [
+                build_ai(
+                    &players[order[0]],
+                    Some(ai_seed(settings.seed, index, game, Player::First)),
+                )?,
+                build_ai(
+                    &players[order[1]],
+                    Some(ai_seed(settings.seed, index, game, Player::Second)),
+                )?,
+            ];
+            let result = play_game(start, &mut ais, settings.max_plies, &adjudicate)?;
+            let a_side = if a_first {
+                Player::First
+            } else {
+                Player::Second
+            };
+            record.add(result.score(a_side));
+            writeln!(
+                output,
+                "{} vs {} game {}: {} ({}-{}-{})",
+                players[order[0]],
+                players[order[1]],
+                game + 1,
+                match result {
+                    GameResult::Win(Player::First) => "1-0",
+                    GameResult::Win(Player::Second) => "0-1",
+                    GameResult::Draw => "1/2",
+                },
+                record.wins,
+                record.draws,
+                record.losses,
+            )
+            .map_err(io)?;
+            if let Some((elo0, elo1)) = sprt {
+                decision = record.sprt(elo0, elo1, settings.alpha, settings.beta);
+                if decision.is_some() {
+                    break;
+                }
+            }
+        }
+        for (player, record) in [(a, record), (b, record.flip())] {
+            let total = &mut totals[player];
+            total.wins += record.wins;
+            total.draws += record.draws;
+            total.losses += record.losses;
+        }
+        pairings.push(Pairing {
+            player: players[a].clone(),
+            opponent: players[b].clone(),
+            record,
+            elo: record.elo(),
+            llr: sprt.and_then(|(elo0, elo1)| record.llr(elo0, elo1)),
+            sprt: decision,
+        });
+    }
+    let standings = players
+        .iter()
+        .zip(totals)
+        .map(|(player, record)| Standing {
+            player: player.clone(),
+            record,
+            score: record.score(),
+            elo: record.elo(),
+        })
+        .collect::<Vec<_>>();
+
+    writeln!(output).map_err(io)?;
+    for p in &pairings {
+        writeln!(
+            output,
+            "{} vs {}: +{} ={} -{} elo {}{}",
+            p.player,
+            p.opponent,
+            p.record.wins,
+            p.record.draws,
+            p.record.losses,
+            format_elo(p.elo),
+            match (p.llr, p.sprt) {
+                (Some(llr), Some(sprt)) => format!(" llr {:.2} ({:?})", llr, sprt),
+                (Some(llr), None) => format!(" llr {:.2}", llr),
+                _ => String::new(),
+            },
+        )
+        .map_err(io)?;
+    }
+    writeln!(output).map_err(io)?;
+    for s in &standings {
+        writeln!(
+            output,
+            "{}: +{} ={} -{} score {:.3} elo {}",
+            s.player,
+            s.record.wins,
+            s.record.draws,
+            s.record.losses,
+            s.score,
+            format_elo(s.elo),
+        )
+        .map_err(io)?;
+    }
+
+    let report = Report {
+        seed: settings.seed,
+        pairings,
+        standings,
+    };
+    if let Some(path) = &settings.csv {
+        fs::write(path, to_csv(&report)).map_err(io)?;
+    }
+    if let Some(path) = &settings.json {
+        let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(io)?;
+    }
+    Ok(report)
+}
+
+/// One row per pairing. Specs containing commas or quotes are quoted.
+pub fn to_csv(report: &Report) -> String {
+    let field = |s: &str| {
+        if s.contains([',', '"', '\n']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_owned()
+        }
+    };
+    let number = |x: Option<f64>| x.map(|x| format!("{:.2}", x)).unwrap_or_default();
+    let mut csv = "player,opponent,wins,draws,losses,elo,elo_low,elo_high,llr,sprt\n".to_owned();
+    for p in &report.pairings {
+        csv += &[
+            field(&p.player),
+            field(&p.opponent),
+            p.record.wins.to_string(),
+            p.record.draws.to_string(),
+            p.record.losses.to_string(),
+            number(p.elo.map(|e| e.estimate)),
+            number(p.elo.map(|e| e.low)),
+            number(p.elo.map(|e| e.high)),
+            number(p.llr),
+            p.sprt.map(|s| format!("{:?}", s)).unwrap_or_default(),
+        ]
+        .join(",");
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use tic_tac_toe::grid::Grid;
+
+    use super::*;
+    use crate::tic_tac_toe::build_ai;
+
+    fn draw(_: &Grid) -> GameResult {
+        GameResult::Draw
+    }
+
+    fn settings(players: &[&str], games: u32) -> Settings {
+        Settings {
+            players: players.iter().map(|&p| p.to_owned()).collect(),
+            games,
+            gauntlet: false,
+            max_plies: 1000,
+            seed: 7,
+            sprt: None,
+            alpha: 0.05,
+            beta: 0.05,
+            csv: None,
+            json: None,
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let grid = Grid::classic();
+        let settings = settings(&["negamax:9:10000", "mcts:30:1.4", "mcts:3:1.4"], 6);
+        let mut log = Vec::new();
+        let report = run(&grid, &settings, build_ai, draw, &mut log).unwrap();
+        assert_eq!(report.pairings.len(), 3);
+        for p in &report.pairings {
+            assert_eq!(p.record.games(), 6);
+        }
+        // Perfect play never loses.
+        assert_eq!(report.standings[0].record.losses, 0);
+        assert_eq!(report.standings[0].record.games(), 12);
+
+        // Every game is seeded.
+        let again = run(&grid, &settings, build_ai, draw, &mut Vec::new()).unwrap();
+        for (p, q) in report.pairings.iter().zip(&again.pairings) {
+            assert_eq!(p.record, q.record);
+        }
+
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.starts_with("negamax:9:10000 vs mcts:30:1.4 game 1: "));
+        assert!(log.contains("mcts:30:1.4 vs negamax:9:10000 game 2: "));
+        let csv = to_csv(&report);
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("negamax:9:10000,mcts:30:1.4,"));
+    }
+
+    #[test]
+    fn ply_cap() {
+        let grid = Grid::classic();
+        let mut ais = [
+            build_ai("search", None).unwrap(),
+            build_ai("search", None).unwrap(),
+        ];
+        let win = |_: &Grid| GameResult::Win(Player::Second);
+        assert_eq!(
+            play_game(&grid, &mut ais, 3, win),
+            Ok(GameResult::Win(Player::Second))
+        );
+        // A game that ends on the last ply allowed needs no adjudication.
+        assert_eq!(play_game(&grid, &mut ais, 9, win), Ok(GameResult::Draw));
+    }
+
+    #[test]
+    fn gauntlet_with_sprt() {
+        let grid = Grid::classic();
+        let mut settings = settings(&["search", "mcts:3:1.4", "mcts:5:1.4"], 200);
+        settings.gauntlet = true;
+        settings.sprt = Some(vec![0.0, 100.0]);
+        let report = run(&grid, &settings, build_ai, draw, &mut Vec::new()).unwrap();
+        assert_eq!(report.pairings.len(), 2);
+        for p in &report.pairings {
+            assert_eq!(p.player, "search");
+            assert_eq!(p.sprt, Some(Sprt::H1));
+            assert!(p.record.games() < 200);
+        }
+
+        settings.sprt = Some(vec![10.0, 0.0]);
+        assert!(run(&grid, &settings, build_ai, draw, &mut Vec::new()).is_err());
+        settings.players.truncate(1);
+        assert!(run(&grid, &settings, build_ai, draw, &mut Vec::new()).is_err());
+        assert!(run(
+            &grid,
+            &self::settings(&["search", "nope"], 2),
+            build_ai,
+            draw,
+            &mut Vec::new()
+        )
+        .is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use game::{hash_of, Game, GameResult, Player};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
@@ -17,12 +18,21 @@ impl Side {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
+impl From<Side> for Player {
+    fn from(side: Side) -> Player {
+        match side {
+            Side::A => Player::First,
+            Side::B => Player::Second,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Board {
     cols: [Vec<Side>; 7],
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Default)]
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Default)]
 pub struct BitBoard {
     a: u64,
     b: u64,
@@ -43,6 +53,18 @@ impl From<Board> for BitBoard {
     }
 }
 
+impl From<&BitBoard> for Board {
+    fn from(bit_board: &BitBoard) -> Board {
+        Board {
+            cols: std::array::from_fn(|x| {
+                (0..bit_board.height(x))
+                    .filter_map(|y| bit_board.get(x, y))
+                    .collect()
+            }),
+        }
+    }
+}
+
 impl BitBoard {
     pub fn new() -> BitBoard {
         Default::default()
@@ -50,7 +72,7 @@ impl BitBoard {
 
     pub fn calc_next(&self) -> Side {
         let b = self.a ^ self.b;
-        if b.count_ones() % 2 == 0 {
+        if b.count_ones().is_multiple_of(2) {
             Side::A
         } else {
             Side::B
@@ -66,29 +88,98 @@ impl BitBoard {
         self.col_val(col) < 0x3f
     }
 
+    /// Number of discs in `col`.
+    pub fn height(&self, col: usize) -> usize {
+        self.col_val(col).count_ones() as usize
+    }
+
+    pub fn get(&self, col: usize, row: usize) -> Option<Side> {
+        let bit = 1 << (row + col * 8);
+        if self.a & bit != 0 {
+            Some(Side::A)
+        } else if self.b & bit != 0 {
+            Some(Side::B)
+        } else {
+            None
+        }
+    }
+
     pub fn list_can_put(&self) -> Vec<usize> {
         (0..7).filter(|&col| self.can_put(col)).collect()
     }
 
+    /// Columns that can still be put in, as bit `col` of the mask.
+    pub fn can_put_mask(&self) -> u8 {
+        col_mask(self.playable())
+    }
+
+    /// Columns where a disc of `side` makes four in a row.
+    pub fn winning_mask(&self, side: Side) -> u8 {
+        col_mask(self.playable() & winning_cells(self.bits(side)))
+    }
+
+    /// Columns where a disc of `side` lets the opponent make four in a row by
+    /// putting a disc right on top of it.
+    pub fn giving_mask(&self, side: Side) -> u8 {
+        let above = (self.playable() << 1) & CELLS;
+        col_mask(above & winning_cells(self.bits(side.flip())))
+    }
+
+    /// Heuristic value of an unfinished game for the side to move: empty cells
+    /// that would complete four in a row, more when they can be played at once,
+    /// and discs in the center column.
+    pub fn evaluate(&self) -> i32 {
+        let empty = !(self.a ^ self.b) & CELLS;
+        let value = |side: Side| {
+            let bits = self.bits(side);
+            let threats = winning_cells(bits) & empty;
+            4 * threats.count_ones() as i32
+                + 4 * (threats & self.playable()).count_ones() as i32
+                + (bits >> 24 & 0xff).count_ones() as i32
+        };
+        let me = self.calc_next();
+        value(me) - value(me.flip())
+    }
+
+    fn bits(&self, side: Side) -> u64 {
+        match side {
+            Side::A => self.a,
+            Side::B => self.b,
+        }
+    }
+
+    /// The lowest empty cell of each column that is not full.
+    fn playable(&self) -> u64 {
+        ((self.a ^ self.b) + BOTTOM) & CELLS
+    }
+
+    /// Puts a disc of `side` in `col` and returns whether `side` has four in a row.
     pub fn put(&mut self, col: usize, side: Side) -> bool {
-        let o = self.col_val(col);
-        let v = (o + 1) << (col * 8);
-        let row = o.count_ones() as usize;
+        let v = (self.col_val(col) + 1) << (col * 8);
         match side {
             Side::A => {
                 self.a += v;
-                is_win(self.a, col, row)
+                has_four(self.a)
             }
             Side::B => {
                 self.b += v;
-                is_win(self.b, col, row)
+                has_four(self.b)
             }
         }
     }
 
+    /// The board flipped left to right.
+    pub fn mirror(&self) -> BitBoard {
+        // Column `x` is byte `x`; byte 7 is always empty.
+        BitBoard {
+            a: self.a.swap_bytes() >> 8,
+            b: self.b.swap_bytes() >> 8,
+        }
+    }
+
     pub fn is_full(&self) -> bool {
         let b = self.a ^ self.b;
-        b == 0x3f3f3f3f3f3f3f
+        b == CELLS
     }
 
     pub fn calc_winner(&self) -> Option<Side> {
@@ -120,6 +211,87 @@ impl BitBoard {
     }
 }
 
+impl Game for BitBoard {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        self.calc_next().into()
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.result().is_some() {
+            Vec::new()
+        } else {
+            self.list_can_put()
+        }
+    }
+
+    fn apply(&mut self, col: usize) {
+        self.put(col, self.calc_next());
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        if has_four(self.a) {
+            Some(GameResult::Win(Player::First))
+        } else if has_four(self.b) {
+            Some(GameResult::Win(Player::Second))
+        } else if self.is_full() {
+            Some(GameResult::Draw)
+        } else {
+            None
+        }
+    }
+
+    fn hash_key(&self) -> u64 {
+        hash_of(self)
+    }
+}
+
+/// Every cell of the board.
+const CELLS: u64 = 0x3f3f3f3f3f3f3f;
+/// The bottom cell of each column.
+const BOTTOM: u64 = 0x01010101010101;
+
+/// Columns with any of `cells`, as bit `col` of the mask.
+fn col_mask(cells: u64) -> u8 {
+    let mut mask = 0;
+    for col in 0..7 {
+        if cells >> (col * 8) & 0xff != 0 {
+            mask |= 1 << col;
+        }
+    }
+    mask
+}
+
+/// Cells that would complete four in a row of `board`, whether empty or not.
+fn winning_cells(board: u64) -> u64 {
+    // vertical: only the three below
+    let mut r = (board << 1) & (board << 2) & (board << 3);
+    // horizontal, and the two diagonals: three on one side, or two and one
+    for shift in [8, 9, 7] {
+        let m = (board << shift) & (board << (shift * 2));
+        r |= m & (board << (shift * 3));
+        r |= m & (board >> shift);
+        let m = (board >> shift) & (board >> (shift * 2));
+        r |= m & (board >> (shift * 3));
+        r |= m & (board << shift);
+    }
+    r & CELLS
+}
+
+/// Whether `board` has four in a row anywhere. Each column takes 8 bits of which
+/// only the lower 6 are used, so shifts never wrap from one column into the next.
+fn has_four(board: u64) -> bool {
+    // vertical, horizontal, and the two diagonals
+    for shift in [1, 8, 9, 7] {
+        let m = board & (board >> shift);
+        if m & (m >> (shift * 2)) != 0 {
+            return true;
+        }
+    }
+    false
+}
+
 fn is_win(board: u64, col: usize, row: usize) -> bool {
     for &mask in FOUR[col][row].iter() {
         if board & mask == mask {
@@ -270,6 +442,114 @@ mod tests {
         assert_eq!(put(4, A, &mut board), (Some(A), true, false));
     }
 
+    #[test]
+    fn can_put_mask() {
+        let mut board = BitBoard::new();
+        assert_eq!(board.can_put_mask(), 0b111_1111);
+        for _ in 0..6 {
+            board.put(2, board.calc_next());
+        }
+        assert_eq!(board.can_put_mask(), 0b111_1011);
+    }
+
+    #[test]
+    fn winning_mask() {
+        let mut board = BitBoard::new();
+        for col in [1, 1, 2, 2, 3] {
+            board.put(col, board.calc_next());
+        }
+        assert_eq!(board.winning_mask(B), 0);
+        assert_eq!(board.winning_mask(A), 0b10001);
+        board.put(0, B);
+        assert_eq!(board.winning_mask(A), 0b10000);
+        for col in [6, 6, 6] {
+            board.put(col, B);
+        }
+        assert_eq!(board.winning_mask(B), 0b1000000);
+    }
+
+    #[test]
+    fn giving_mask() {
+        // B threatens both ends of the second row, so a disc in column 0 or 4
+        // lets B win right on top of it.
+        let mut board = BitBoard::new();
+        for (col, side) in [(1, A), (1, B), (2, B), (2, B), (3, A), (3, B)] {
+            board.put(col, side);
+        }
+        assert_eq!(board.giving_mask(A), 0b10001);
+        assert_eq!(board.giving_mask(B), 0);
+        assert_eq!(board.winning_mask(B), 0);
+    }
+
+    #[test]
+    fn height_and_get() {
+        let mut board = BitBoard::new();
+        for col in [3, 3, 4] {
+            board.put(col, board.calc_next());
+        }
+        assert_eq!(board.height(3), 2);
+        assert_eq!(board.height(0), 0);
+        assert_eq!(board.get(3, 1), Some(B));
+        assert_eq!(board.get(4, 0), Some(A));
+        assert_eq!(board.get(4, 1), None);
+    }
+
+    #[test]
+    fn game_trait() {
+        let mut board = BitBoard::new();
+        assert_eq!(game::perft(&board, 4), 2401);
+        for col in [0, 6, 1, 6, 2, 6] {
+            board.apply(col);
+        }
+        assert_eq!(board.to_move(), Player::First);
+        assert_eq!(board.result(), None);
+        board.apply(3);
+        assert_eq!(board.result(), Some(GameResult::Win(Player::First)));
+        assert!(Game::legal_moves(&board).is_empty());
+        assert_eq!(game::perft(&board, 3), 1);
+    }
+
+    #[test]
+    fn evaluate() {
+        let mut board = BitBoard::new();
+        for col in [0, 6, 1, 6, 2] {
+            board.put(col, board.calc_next());
+        }
+        // B faces a playable threat of A.
+        assert_eq!(board.evaluate(), -8);
+        assert_eq!(board.mirror().evaluate(), -8);
+
+        let mut negamax = game::negamax::Negamax::new(BitBoard::evaluate, 6);
+        assert_eq!(negamax.search(&board).best, Some(3));
+        board.put(5, B);
+        let info = negamax.search(&board);
+        assert_eq!(info.best, Some(3));
+        assert_eq!(game::negamax::mate_plies(info.score), Some(1));
+    }
+
+    #[test]
+    fn mirror() {
+        let mut board = BitBoard::new();
+        let mut mirror = BitBoard::new();
+        for col in [0, 1, 1, 3, 6, 6, 2] {
+            board.put(col, board.calc_next());
+            mirror.put(6 - col, mirror.calc_next());
+        }
+        assert_eq!(board.mirror(), mirror);
+        assert_eq!(mirror.mirror(), board);
+    }
+
+    #[test]
+    fn to_board() {
+        let mut bit_board = BitBoard::new();
+        for col in [3, 3, 0, 6, 3] {
+            bit_board.apply(col);
+        }
+        let board = Board::from(&bit_board);
+        assert_eq!(board.cols[3], vec![A, B, A]);
+        assert_eq!(BitBoard::from(board), bit_board);
+    }
+
     #[test]
     fn is_full() {
         let mut board = BitBoard::new();
@@ -1,13 +1,27 @@
 mod board;
 mod mctree;
+#[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+pub mod parallel;
 
+use std::time::Duration;
+
+use game::{
+    analysis::{analyze, mcts_evaluation, Annotation, Thresholds},
+    history::History,
+    mcts::Mcts,
+    negamax::{mate_plies, Negamax},
+    record::GameRecord,
+};
 use rand::{rngs::SmallRng, SeedableRng};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 
 pub use crate::board::*;
-use crate::mctree::McTreeAI;
+pub use crate::mctree::{
+    generic_search, BoardPlayout, McTreeAI, MoveStats, Outcome, Playout, SearchResult, Selection,
+    DEFAULT_MAX_NODES,
+};
 
 #[wasm_bindgen(js_name = calculateWinner)]
 pub fn js_calculate_winner(board: &JsValue) -> Result<JsValue, JsValue> {
@@ -31,14 +45,29 @@ pub fn js_calculate_winner(board: &JsValue) -> Result<JsValue, JsValue> {
 pub struct SearchResponse {
     pub position: Option<u32>,
     pub score: String,
+    /// `"win in N"` or `"loss in N"` plies for the side to move once proven.
+    pub result: Option<String>,
+}
+
+impl From<Option<SearchResult>> for SearchResponse {
+    fn from(result: Option<SearchResult>) -> SearchResponse {
+        match result {
+            Some(r) => SearchResponse {
+                position: Some(r.col as u32),
+                score: r.point.to_string(),
+                result: r.outcome.describe(),
+            },
+            None => SearchResponse {
+                position: None,
+                score: "".to_owned(),
+                result: None,
+            },
+        }
+    }
 }
 
 fn none_response() -> JsValue {
-    to_value(&SearchResponse {
-        position: None,
-        score: "".to_owned(),
-    })
-    .unwrap()
+    to_value(&SearchResponse::from(None)).unwrap()
 }
 
 fn gen_rng() -> SmallRng {
@@ -52,35 +81,359 @@ fn gen_rng() -> SmallRng {
     SmallRng::seed_from_u64(seed)
 }
 
+/// Optional knobs of [`McTreeAI`], all off by default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McTreeOptions {
+    pub max_nodes: Option<u32>,
+    pub transpositions: Option<bool>,
+    /// `"random"` or `"tactical"`.
+    pub playout: Option<String>,
+    /// `"weighted"`, `"ucb1"`, `"ucb1-tuned"` or `"puct"`.
+    pub selection: Option<String>,
+    /// Search with the generic engine of [`game::mcts`], which only takes
+    /// `maxNodes` and `playout`.
+    pub generic: Option<bool>,
+}
+
+/// Reads the optional last argument of `mctree`; `undefined` and `null` mean
+/// the defaults.
+fn mctree_options(options: Option<JsValue>) -> Result<McTreeOptions, JsValue> {
+    match options {
+        Some(options) => Ok(from_value::<Option<_>>(options)?.unwrap_or_default()),
+        None => Ok(McTreeOptions::default()),
+    }
+}
+
+fn build_mctree(
+    limit: u32,
+    expansion_threshold: u32,
+    c: f64,
+    options: McTreeOptions,
+) -> Result<McTreeAI<SmallRng>, String> {
+    let mut ai = McTreeAI::new(gen_rng(), limit as u64, expansion_threshold, c);
+    if let Some(max_nodes) = options.max_nodes {
+        ai = ai.with_max_nodes(max_nodes as usize);
+    }
+    if let Some(transpositions) = options.transpositions {
+        ai = ai.with_transpositions(transpositions);
+    }
+    if let Some(playout) = options.playout {
+        ai = ai.with_playout(playout.parse::<Playout>()?);
+    }
+    if let Some(selection) = options.selection {
+        ai = ai.with_selection(selection.parse::<Selection>()?);
+    }
+    Ok(ai)
+}
+
+fn generic_mctree(
+    board: &BitBoard,
+    limit: u32,
+    expansion_threshold: u32,
+    c: f64,
+    options: McTreeOptions,
+) -> Result<Option<SearchResult>, String> {
+    let playout = match options.playout {
+        Some(playout) => playout.parse::<Playout>()?,
+        None => Playout::Random,
+    };
+    let mut mcts = Mcts::new(board, BoardPlayout::new(gen_rng(), playout), c)
+        .with_expansion_threshold(expansion_threshold);
+    if let Some(max_nodes) = options.max_nodes {
+        mcts = mcts.with_max_nodes(max_nodes as usize);
+    }
+    Ok(generic_search(
+        &mut mcts,
+        Duration::from_millis(limit as u64),
+    ))
+}
+
+/// `options` is an optional [`McTreeOptions`] object.
 #[wasm_bindgen(js_name = mctree)]
 pub fn js_mctree(
     board: &JsValue,
     limit: u32,
     expansion_threshold: u32,
     c: f64,
+    options: Option<JsValue>,
 ) -> Result<JsValue, JsValue> {
     let board: Board = from_value(board.clone())?;
     let board = BitBoard::from(board);
-    if board.is_full() {
+    if board.is_full() || board.calc_winner().is_some() {
         return Ok(none_response());
     }
-    let mut ai = McTreeAI::new(gen_rng(), limit as u64, expansion_threshold, c);
-    let (position, score) = ai.search(&board);
-    if !board.can_put(position) {
+    let options = mctree_options(options)?;
+    if options.generic == Some(true) {
+        let result = generic_mctree(&board, limit, expansion_threshold, c, options)
+            .map_err(|e| JsValue::from_str(&e))?;
+        return Ok(to_value(&SearchResponse::from(result))?);
+    }
+    let mut ai =
+        build_mctree(limit, expansion_threshold, c, options).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&SearchResponse::from(ai.search(&board)))?)
+}
+
+fn negamax(board: &BitBoard, depth: u32, limit: u32) -> SearchResponse {
+    let info = Negamax::new(BitBoard::evaluate, depth)
+        .with_time_limit(Duration::from_millis(limit as u64))
+        .search(board);
+    let outcome = match mate_plies(info.score) {
+        Some(plies) if info.score > 0 => Outcome::Win(plies),
+        Some(plies) => Outcome::Loss(plies),
+        None => Outcome::Unknown,
+    };
+    SearchResponse {
+        position: info.best.map(|col| col as u32),
+        score: info.score.to_string(),
+        result: outcome.describe(),
+    }
+}
+
+/// Alpha-beta search up to `depth` plies or `limit` milliseconds, scored by
+/// [`BitBoard::evaluate`]. `score` is the negamax score for the side to move.
+#[wasm_bindgen(js_name = negamax)]
+pub fn js_negamax(board: &JsValue, depth: u32, limit: u32) -> Result<JsValue, JsValue> {
+    let board: Board = from_value(board.clone())?;
+    let board = BitBoard::from(board);
+    if board.is_full() || board.calc_winner().is_some() {
         return Ok(none_response());
     }
-    Ok(to_value(&SearchResponse {
-        position: Some(position as u32),
-        score: score.to_string(),
-    })?)
+    Ok(to_value(&negamax(&board, depth, limit))?)
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepResponse {
+    /// Best column so far, `null` until the root is expanded or once the game is over.
+    pub position: Option<u32>,
+    pub score: String,
+    pub result: Option<String>,
+    /// Visit count of each column.
+    pub visits: [u32; 7],
+    /// Whether the result of the position is known, so that more steps are useless.
+    pub solved: bool,
+}
+
+/// An MCTS player that keeps its tree between moves, so that it can go on
+/// thinking in small slices with [`Searcher::ponder`] while the opponent is to
+/// move.
+#[wasm_bindgen]
+pub struct Searcher {
+    ai: McTreeAI<SmallRng>,
+}
+
+#[wasm_bindgen]
+impl Searcher {
+    /// Same parameters as `mctree`, with the tree rooted at `board`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        board: &JsValue,
+        limit: u32,
+        expansion_threshold: u32,
+        c: f64,
+        options: Option<JsValue>,
+    ) -> Result<Searcher, JsValue> {
+        let board: Board = from_value(board.clone())?;
+        let options = mctree_options(options)?;
+        let mut ai = build_mctree(limit, expansion_threshold, c, options)
+            .map_err(|e| JsValue::from_str(&e))?;
+        ai.reset(&BitBoard::from(board));
+        Ok(Searcher { ai })
+    }
+
+    /// Runs `iterations` playouts from the current position.
+    pub fn ponder(&mut self, iterations: u32) {
+        self.ai.run(iterations);
+    }
+
+    /// Runs `iterations` playouts and reports the search so far, so that the caller
+    /// can show progress and stop at any time.
+    pub fn step(&mut self, iterations: u32) -> Result<JsValue, JsValue> {
+        self.ai.run(iterations);
+        let root = self.ai.root();
+        let best = self
+            .ai
+            .best()
+            .filter(|_| !root.is_full() && root.calc_winner().is_none());
+        let SearchResponse {
+            position,
+            score,
+            result,
+        } = best.into();
+        Ok(to_value(&StepResponse {
+            position,
+            score,
+            result,
+            visits: self.ai.visits(),
+            solved: self.ai.is_solved(),
+        })?)
+    }
+
+    /// The top `depth` levels of the tree as nested objects with `col`, `visits`,
+    /// `winRate`, `result` and `children`.
+    #[wasm_bindgen(js_name = exportTree)]
+    pub fn export_tree(&self, depth: u32) -> Result<JsValue, JsValue> {
+        Ok(to_value(&self.ai.export(depth as usize))?)
+    }
+
+    /// The top `depth` levels of the tree in Graphviz DOT.
+    #[wasm_bindgen(js_name = exportDot)]
+    pub fn export_dot(&self, depth: u32) -> String {
+        self.ai.export_dot(depth as usize)
+    }
+
+    /// Puts a disc in `col` for the side to move, keeping the subtree of the
+    /// move.
+    pub fn play(&mut self, col: u32) -> Result<(), JsValue> {
+        let root = self.ai.root();
+        if col >= 7 || !root.can_put(col as usize) || root.calc_winner().is_some() {
+            return Err(JsValue::from_str(&format!("cannot put in {}", col)));
+        }
+        self.ai.advance(col as usize);
+        Ok(())
+    }
+
+    /// Thinks for the time limit on top of the kept tree and returns the best
+    /// move like `mctree`. The move is not played; call [`Searcher::play`] with it.
+    pub fn search(&mut self) -> Result<JsValue, JsValue> {
+        Ok(to_value(&SearchResponse::from(self.ai.think()))?)
+    }
+}
+
+/// A game from the empty board with its moves, so that the frontend can undo,
+/// redo and jump to any ply without replaying the moves itself.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct GameHistory {
+    history: History<BitBoard>,
+}
+
+#[wasm_bindgen]
+impl GameHistory {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GameHistory {
+        GameHistory::default()
+    }
+
+    /// The current board in the form taken by `calculateWinner`.
+    pub fn board(&self) -> Result<JsValue, JsValue> {
+        Ok(to_value(&Board::from(self.history.current()))?)
+    }
+}
+
+game::history_methods! {
+    GameHistory,
+    /// Puts a disc in `col` for the side to move.
+    play(col)
+}
+
+/// Rules of a [`Record`]; Connect Four has only the 7x6 board.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Variant {}
+
+/// A played game, with the column of each move.
+pub type Record = GameRecord<Variant, usize>;
+
+pub const RECORD_GAME: &str = "connect-four";
+
+/// Reads a [`Record`], checking its moves and result.
+pub fn parse_record(json: &str) -> Result<Record, String> {
+    Record::parse(json, RECORD_GAME, |_| Ok(BitBoard::new()))
+}
+
+/// Writes a [`Record`] after the checks of [`parse_record`].
+pub fn emit_record(record: &Record) -> Result<String, String> {
+    record.emit(RECORD_GAME, |_| Ok(BitBoard::new()))
+}
+
+#[wasm_bindgen(js_name = parseRecord)]
+pub fn js_parse_record(json: &str) -> Result<JsValue, JsValue> {
+    let record = parse_record(json).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&record)?)
+}
+
+#[wasm_bindgen(js_name = emitRecord)]
+pub fn js_emit_record(record: &JsValue) -> Result<String, JsValue> {
+    let record: Record = from_value(record.clone())?;
+    emit_record(&record).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Annotates every move of `record` by the generic MCTS with tactical playouts,
+/// `iterations` per position. Proven positions are scored exactly.
+pub fn analyze_game(record: &Record, iterations: u32) -> Result<Vec<Annotation<usize>>, String> {
+    let mut rng = match record.seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    };
+    analyze(
+        &BitBoard::new(),
+        &record.move_list(),
+        |board| {
+            let playout =
+                BoardPlayout::new(SmallRng::from_rng(&mut rng).unwrap(), Playout::Tactical);
+            let mut mcts = Mcts::new(board, playout, 2.0);
+            mcts.run(iterations);
+            mcts_evaluation(&mcts)
+        },
+        &Thresholds::default(),
+    )
+}
+
+#[wasm_bindgen(js_name = analyzeGame)]
+pub fn js_analyze_game(record: &JsValue, iterations: u32) -> Result<JsValue, JsValue> {
+    let record: Record = from_value(record.clone())?;
+    let annotations = analyze_game(&record, iterations).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&annotations)?)
 }
 
-#[cfg(target_arch = "wasm32")]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use game::{record::Outcome, Game};
+    #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::*;
 
+    #[test]
+    fn record() {
+        let mut record = Record::new(RECORD_GAME, Variant {});
+        record.players[1].ai = Some("mctree:1000:2:2.0".to_owned());
+        let mut board = BitBoard::new();
+        for col in [0, 1, 0, 1, 0, 1, 0] {
+            board.apply(col);
+            record.push(&board, col, None);
+        }
+        assert_eq!(record.result, Some(Outcome::First));
+        let json = emit_record(&record).unwrap();
+        assert_eq!(parse_record(&json).unwrap(), record);
+
+        record.moves.pop();
+        assert!(emit_record(&record).is_err());
+        record.result = None;
+        record.moves[0].mv = 7;
+        assert!(emit_record(&record).is_err());
+    }
+
+    #[test]
+    fn analysis() {
+        use game::analysis::Classification;
+
+        let mut record = Record::new(RECORD_GAME, Variant {});
+        record.seed = Some(1);
+        let mut board = BitBoard::new();
+        // B lets A complete the first column.
+        for col in [0, 1, 0, 1, 0, 2] {
+            board.apply(col);
+            record.push(&board, col, None);
+        }
+        let annotations = analyze_game(&record, 2000).unwrap();
+        assert_eq!(annotations.len(), 6);
+        let blunder = &annotations[5];
+        assert_eq!(blunder.best, Some(0));
+        assert_eq!(blunder.after, 0.0);
+        assert_eq!(blunder.classification, Classification::Blunder);
+    }
+
+    #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn smoke_mctree() {
         let board = js_sys::JSON::parse(
@@ -97,6 +450,52 @@ mod tests {
 }"#,
         )
         .unwrap();
-        js_mctree(&board, 10, 2, 2.0).unwrap();
+        js_mctree(&board, 10, 2, 2.0, None).unwrap();
+        js_mctree(&board, 10, 2, 2.0, Some(JsValue::NULL)).unwrap();
+        for options in [
+            r#"{"maxNodes": 100}"#,
+            r#"{"transpositions": true}"#,
+            r#"{"playout": "tactical"}"#,
+            r#"{"selection": "puct"}"#,
+            r#"{"generic": true, "playout": "tactical"}"#,
+        ] {
+            let options = js_sys::JSON::parse(options).unwrap();
+            js_mctree(&board, 10, 2, 2.0, Some(options)).unwrap();
+        }
+        let options = js_sys::JSON::parse(r#"{"playout": "greedy"}"#).unwrap();
+        assert!(js_mctree(&board, 10, 2, 2.0, Some(options)).is_err());
+        js_negamax(&board, 6, 10).unwrap();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn ponder() {
+        let board = js_sys::JSON::parse(r#"{"cols": [[], [], [], [], [], [], []]}"#).unwrap();
+        let mut searcher = Searcher::new(&board, 10, 2, 2.0, None).unwrap();
+        searcher.play(3).unwrap();
+        searcher.ponder(1000);
+        searcher.play(3).unwrap();
+        searcher.step(100).unwrap();
+        searcher.export_tree(2).unwrap();
+        assert!(searcher.export_dot(2).starts_with("digraph"));
+        assert!(searcher.play(7).is_err());
+        searcher.search().unwrap();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn history() {
+        let mut history = GameHistory::new();
+        for col in [3, 3, 4] {
+            history.play(col).unwrap();
+        }
+        assert!(history.play(7).is_err());
+        assert!(history.undo());
+        history.jump_to(0).unwrap();
+        assert!(history.jump_to(4).is_err());
+        assert!(history.redo());
+        let board: Board = from_value(history.board().unwrap()).unwrap();
+        assert_eq!(BitBoard::from(board).to_move(), game::Player::Second);
+        history.status().unwrap();
     }
 }
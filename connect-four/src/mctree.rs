@@ -1,8 +1,12 @@
-use std::time::Duration;
-#[cfg(not(target_arch = "wasm32"))]
-use std::time::Instant;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    str::FromStr,
+    time::Duration,
+};
 
-use rand::{seq::SliceRandom, Rng};
+use game::{mcts::Mcts, time::Instant, Game, GameResult};
+use rand::Rng;
+use serde::Serialize;
 
 use crate::{BitBoard, Side};
 
@@ -10,6 +14,11 @@ const WIN_POINT: f64 = 1.0;
 const LOSE_POINT: f64 = 0.0;
 const DRAW_POINT: f64 = 0.5;
 
+/// Default cap on the number of nodes in the tree, and on that of edges. Nodes
+/// take 20 bytes and edges 12, about 64 MiB in all, and up to 100 MiB more for
+/// the table of transpositions when shared.
+pub const DEFAULT_MAX_NODES: usize = 1 << 21;
+
 fn choice_with_weight<R: Rng>(rng: &mut R, weight: &[f64]) -> usize {
     let sum = weight.iter().fold(0.0, |x, y| x + *y);
     let r = rng.gen_range(0.0..sum);
@@ -23,15 +32,69 @@ fn choice_with_weight<R: Rng>(rng: &mut R, weight: &[f64]) -> usize {
     weight.len() - 1
 }
 
-fn random_down<R: Rng>(rng: &mut R, board: &BitBoard, side: Side) -> f64 {
-    let mut board = board.clone();
+/// Picks a uniformly random set bit of a non-zero column mask.
+fn choose_col<R: Rng>(rng: &mut R, mask: u8) -> usize {
+    let mut k = rng.gen_range(0..mask.count_ones());
+    let mut m = mask;
+    loop {
+        let col = m.trailing_zeros();
+        if k == 0 {
+            return col as usize;
+        }
+        k -= 1;
+        m &= m - 1;
+    }
+}
+
+/// How playouts choose their moves.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Playout {
+    /// Any column, uniformly at random.
+    Random,
+    /// Takes an immediate win, blocks the opponent's immediate win, and otherwise
+    /// avoids columns that let the opponent win right on top of the disc.
+    Tactical,
+}
+
+impl FromStr for Playout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Playout, String> {
+        match s {
+            "random" => Ok(Playout::Random),
+            "tactical" => Ok(Playout::Tactical),
+            _ => Err(format!("Unknown playout: {}", s)),
+        }
+    }
+}
+
+/// Plays `board` out to the end in place and returns the point of `side`, the
+/// side to move.
+fn random_down<R: Rng>(rng: &mut R, board: &mut BitBoard, side: Side, playout: Playout) -> f64 {
     let mut s = side;
     loop {
-        if board.is_full() {
+        let mask = board.can_put_mask();
+        if mask == 0 {
             return DRAW_POINT;
         }
-        let can = board.list_can_put();
-        let col = *can.choose(rng).expect("no next");
+        let mask = match playout {
+            Playout::Random => mask,
+            Playout::Tactical => {
+                let win = board.winning_mask(s);
+                let block = board.winning_mask(s.flip());
+                let safe = mask & !board.giving_mask(s);
+                if win != 0 {
+                    win
+                } else if block != 0 {
+                    block
+                } else if safe != 0 {
+                    safe
+                } else {
+                    mask
+                }
+            }
+        };
+        let col = choose_col(rng, mask);
         if board.put(col, s) {
             return if s == side { WIN_POINT } else { LOSE_POINT };
         }
@@ -39,32 +102,268 @@ fn random_down<R: Rng>(rng: &mut R, board: &BitBoard, side: Side) -> f64 {
     }
 }
 
+/// How a child is picked from the values of the children.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Selection {
+    /// At random with weights proportional to UCB1 with the root's visit count.
+    Weighted,
+    /// The child with the highest UCB1.
+    Ucb1,
+    /// The child with the highest UCB1-Tuned, which bounds the exploration term by
+    /// the variance of the results. `c` is not used.
+    Ucb1Tuned,
+    /// The child with the highest PUCT value, weighting exploration by heuristic
+    /// priors: take a win, block the opponent's win, prefer the center, and avoid
+    /// giving a win right on top of the disc.
+    Puct,
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Selection, String> {
+        match s {
+            "weighted" => Ok(Selection::Weighted),
+            "ucb1" => Ok(Selection::Ucb1),
+            "ucb1-tuned" => Ok(Selection::Ucb1Tuned),
+            "puct" => Ok(Selection::Puct),
+            _ => Err(format!("Unknown selection: {}", s)),
+        }
+    }
+}
+
+/// Priors of the columns of `board` for `side` to move, summing to 1 over the
+/// columns that can be put in.
+fn priors(board: &BitBoard, side: Side) -> [f32; 7] {
+    const CENTER: [f32; 7] = [1.0, 2.0, 3.0, 4.0, 3.0, 2.0, 1.0];
+    let mask = board.can_put_mask();
+    let win = board.winning_mask(side);
+    let block = board.winning_mask(side.flip());
+    let giving = board.giving_mask(side);
+    let mut priors = [0.0; 7];
+    for (col, p) in priors.iter_mut().enumerate() {
+        let bit = 1 << col;
+        if mask & bit == 0 {
+            continue;
+        }
+        *p = CENTER[col];
+        if win & bit != 0 {
+            *p *= 16.0;
+        } else if block & bit != 0 {
+            *p *= 8.0;
+        } else if giving & bit != 0 {
+            *p /= 8.0;
+        }
+    }
+    let sum: f32 = priors.iter().sum();
+    priors.map(|p| p / sum)
+}
+
+/// Proven result of a node for the side to move there, with the number of plies
+/// until the game ends under best play: the fastest win and the slowest loss.
+/// Until the node is exact, the plies are those of the lines proven so far and
+/// may still shrink.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Solved {
+    Unknown,
+    Win(u8),
+    Lose(u8),
+}
+
+impl Solved {
+    fn point(self) -> Option<f64> {
+        match self {
+            Solved::Unknown => None,
+            Solved::Win(_) => Some(WIN_POINT),
+            Solved::Lose(_) => Some(LOSE_POINT),
+        }
+    }
+
+    /// The outcome for the side whose move led to a node with this result.
+    fn mover_outcome(self) -> Outcome {
+        match self {
+            Solved::Unknown => Outcome::Unknown,
+            Solved::Win(n) => Outcome::Loss(n as u32 + 1),
+            Solved::Lose(n) => Outcome::Win(n as u32 + 1),
+        }
+    }
+}
+
+/// Proven outcome of a move for the side playing it, counting plies from the
+/// move itself: a move that makes four in a row is a win in 1.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Unknown,
+    Win(u32),
+    Loss(u32),
+}
+
+impl Outcome {
+    /// Order of preference among moves: proven wins, fastest first, then unknown
+    /// moves by visit count, then proven losses, slowest first.
+    pub fn rank(self, visits: u32) -> (u8, i64) {
+        match self {
+            Outcome::Win(n) => (2, -(n as i64)),
+            Outcome::Unknown => (1, visits as i64),
+            Outcome::Loss(n) => (0, n as i64),
+        }
+    }
+
+    /// `"win in N"` or `"loss in N"`, `None` while unknown.
+    pub fn describe(self) -> Option<String> {
+        match self {
+            Outcome::Unknown => None,
+            Outcome::Win(n) => Some(format!("win in {}", n)),
+            Outcome::Loss(n) => Some(format!("loss in {}", n)),
+        }
+    }
+}
+
+/// The move chosen by a search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchResult {
+    pub col: usize,
+    /// Estimated point of the move for the side to move.
+    pub point: f64,
+    pub outcome: Outcome,
+}
+
+/// The smaller of `board` and its mirror image, which share a node in a DAG.
+fn canonical(board: &BitBoard) -> BitBoard {
+    let mirror = board.mirror();
+    if mirror < *board {
+        mirror
+    } else {
+        board.clone()
+    }
+}
+
+/// The column `col` of a position as seen from its mirror image, if `flipped`.
+fn orient(col: usize, flipped: bool) -> usize {
+    if flipped {
+        6 - col
+    } else {
+        col
+    }
+}
+
+/// A node of the tree, stored in [`McTreeAI`]'s arena.
+///
+/// The board is not kept; it is replayed from the root along the edges while
+/// descending. Edges to the children of a node are stored contiguously from
+/// `first_edge`.
 #[derive(Debug, Clone)]
 struct Node {
     visited_count: u32,
-    win_point: f64,
-    board: BitBoard,
-    result: Option<f64>,
-    children: Vec<Node>,
+    win_point: f32,
+    /// Sum of the squared points, for the variance of UCB1-Tuned.
+    square_point: f32,
+    first_edge: u32,
+    edges_len: u8,
+    result: Solved,
+    /// Whether `result` is final, plies included, so that the node needs no more
+    /// search. A won node is searched on until no other move can win faster, and
+    /// a draw is final without being a result.
+    exact: bool,
 }
 
-pub struct McTreeAI<R> {
-    rng: R,
-    limit: Duration,
-    expansion_threshold: u32,
-    c: f64,
+/// A move from a node. With transpositions, a node is shared with the mirror
+/// image of its position, so `col` is the column in the canonical one of the two.
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    col: u8,
+    node: u32,
+    /// Prior of the move for [`Selection::Puct`].
+    prior: f32,
+}
+
+impl Edge {
+    fn new(col: usize, node: u32, prior: f32) -> Edge {
+        Edge {
+            col: col as u8,
+            node,
+            prior,
+        }
+    }
 }
 
 impl Node {
-    fn new(board: BitBoard, is_lose: bool) -> Node {
+    fn new(result: Solved) -> Node {
         Node {
             visited_count: 0,
             win_point: 0.0,
-            board,
-            result: if is_lose { Some(LOSE_POINT) } else { None },
-            children: Vec::new(),
+            square_point: 0.0,
+            first_edge: 0,
+            edges_len: 0,
+            result,
+            exact: result != Solved::Unknown,
         }
     }
+
+    fn add_point(&mut self, point: f64) {
+        self.win_point += point as f32;
+        self.square_point += (point * point) as f32;
+    }
+
+    fn edges(&self) -> std::ops::Range<usize> {
+        let first = self.first_edge as usize;
+        first..first + self.edges_len as usize
+    }
+}
+
+/// Statistics of a move from the root, from the point of view of the side to
+/// move there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveStats {
+    pub col: usize,
+    pub visits: u32,
+    /// Sum of the points of the playouts through the move.
+    pub point: f64,
+    pub outcome: Outcome,
+    /// Whether `outcome`, plies included, is final. Until then its plies are
+    /// those of the lines proven so far and may still shrink.
+    pub exact: bool,
+}
+
+/// A node of the searched tree, for visualization.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeExport {
+    /// The move into the node, `None` at the root.
+    pub col: Option<usize>,
+    pub visits: u32,
+    /// Win rate of the side that moved into the node, `None` when not visited.
+    pub win_rate: Option<f64>,
+    /// `"win in N"` or `"loss in N"` for the side that moved into the node once
+    /// solved, counting plies from that move.
+    pub result: Option<String>,
+    pub children: Vec<TreeExport>,
+}
+
+/// Monte Carlo tree search specialized for [`BitBoard`].
+///
+/// It is not built on the generic engine of [`game::mcts`], whose nodes each own
+/// a clone of their position: the transpositions over mirror images, the
+/// selections and the node cap here rely on nodes of 20 bytes and edges of 12
+/// keyed by column. [`generic_search`] runs the generic engine instead, with the
+/// `generic` option of `mctree` in the frontend and `generic-n` in bodoge.
+pub struct McTreeAI<R> {
+    rng: R,
+    limit: Duration,
+    expansion_threshold: u32,
+    c: f64,
+    max_nodes: usize,
+    playout: Playout,
+    selection: Selection,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    /// Node of each position, up to left-right mirroring, when transpositions are
+    /// shared.
+    transpositions: Option<HashMap<BitBoard, u32>>,
+    /// Position of node 0.
+    root: BitBoard,
+    /// Playouts run from the root, for the weighted selection.
+    total_count: u64,
 }
 
 impl<R: Rng> McTreeAI<R> {
@@ -74,162 +373,1017 @@ impl<R: Rng> McTreeAI<R> {
             limit: Duration::from_millis(limit),
             expansion_threshold,
             c,
+            max_nodes: DEFAULT_MAX_NODES,
+            playout: Playout::Random,
+            selection: Selection::Weighted,
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            transpositions: None,
+            root: BitBoard::new(),
+            total_count: 0,
         }
     }
 
-    fn choice_child(&mut self, log_total_count: f64, node: &Node) -> usize {
-        let mut weight = Vec::with_capacity(node.children.len());
-        for (i, child) in node.children.iter().enumerate() {
-            if child.visited_count == 0 {
+    /// Caps the number of nodes, and that of edges, in the tree. Once reached,
+    /// leaves are no longer expanded and keep being evaluated by playouts.
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> McTreeAI<R> {
+        self.max_nodes = max_nodes.max(1);
+        self
+    }
+
+    pub fn with_playout(mut self, playout: Playout) -> McTreeAI<R> {
+        self.playout = playout;
+        self
+    }
+
+    pub fn with_selection(mut self, selection: Selection) -> McTreeAI<R> {
+        self.selection = selection;
+        self
+    }
+
+    /// Shares one node between all move orders, and mirror images, reaching the
+    /// same position, which turns the tree into a DAG.
+    pub fn with_transpositions(mut self, enabled: bool) -> McTreeAI<R> {
+        self.transpositions = if enabled { Some(HashMap::new()) } else { None };
+        self
+    }
+
+    /// Chooses the edge to descend from `node`. Below a proven node, only edges to
+    /// children whose result may still change are candidates.
+    fn choice_child(&mut self, log_total_count: f64, node: usize) -> usize {
+        let proven = self.nodes[node].result != Solved::Unknown;
+        let parent_count = self.nodes[node].visited_count as f64;
+        let mut candidates = Vec::with_capacity(7);
+        let mut weight = Vec::with_capacity(7);
+        for i in self.nodes[node].edges() {
+            let edge = &self.edges[i];
+            let child = &self.nodes[edge.node as usize];
+            if proven && child.exact {
+                continue;
+            }
+            if child.visited_count == 0 && self.selection != Selection::Puct {
                 return i;
             }
-            let a = 1.0 - child.win_point / child.visited_count as f64;
-            let b = self.c * (log_total_count / child.visited_count as f64).sqrt();
+            let n = child.visited_count as f64;
+            let a = if n == 0.0 {
+                DRAW_POINT
+            } else {
+                1.0 - child.win_point as f64 / n
+            };
+            let b = match self.selection {
+                Selection::Weighted => self.c * (log_total_count / n).sqrt(),
+                Selection::Ucb1 => self.c * (parent_count.ln() / n).sqrt(),
+                Selection::Ucb1Tuned => {
+                    let mean = child.win_point as f64 / n;
+                    let variance = child.square_point as f64 / n - mean * mean;
+                    let ln = parent_count.ln();
+                    let v = variance + (2.0 * ln / n).sqrt();
+                    (ln / n * v.min(0.25)).sqrt()
+                }
+                Selection::Puct => self.c * edge.prior as f64 * parent_count.sqrt() / (1.0 + n),
+            };
+            candidates.push(i);
             weight.push(a + b);
         }
-        choice_with_weight(&mut self.rng, &weight)
+        if self.selection == Selection::Weighted {
+            return candidates[choice_with_weight(&mut self.rng, &weight)];
+        }
+        let best = (0..weight.len())
+            .max_by(|&x, &y| weight[x].total_cmp(&weight[y]))
+            .expect("No children");
+        candidates[best]
+    }
+
+    fn child_node(&mut self, board: &BitBoard, result: Solved) -> u32 {
+        let index = self.nodes.len() as u32;
+        if let Some(table) = self.transpositions.as_mut() {
+            let key = canonical(board);
+            if let Some(&i) = table.get(&key) {
+                return i;
+            }
+            table.insert(key, index);
+        }
+        self.nodes.push(Node::new(result));
+        index
+    }
+
+    /// Whether columns of `board` are mirrored in the edges of its node.
+    fn is_flipped(&self, board: &BitBoard) -> bool {
+        self.transpositions.is_some() && board.mirror() < *board
+    }
+
+    /// Adds the children of `node`. Returns `false` when the node cap leaves no room.
+    fn expand(&mut self, node: usize, board: &BitBoard, side: Side) -> bool {
+        let can = board.list_can_put();
+        // With transpositions, edges outnumber nodes.
+        if self.nodes.len().max(self.edges.len()) + can.len() > self.max_nodes {
+            return false;
+        }
+        let priors = match self.selection {
+            Selection::Puct => priors(board, side),
+            _ => [0.0; 7],
+        };
+        let flipped = self.is_flipped(board);
+        let first = self.edges.len();
+        for col in can {
+            let edge_col = orient(col, flipped);
+            let mut board = board.clone();
+            if board.put(col, side) {
+                self.edges.truncate(first);
+                let child = self.child_node(&board, Solved::Lose(0));
+                self.nodes[child as usize].visited_count += 1;
+                self.edges.push(Edge::new(edge_col, child, 1.0));
+                break;
+            }
+            let child = self.child_node(&board, Solved::Unknown);
+            self.edges.push(Edge::new(edge_col, child, priors[col]));
+        }
+        let len = self.edges.len() - first;
+        let n = &mut self.nodes[node];
+        n.first_edge = first as u32;
+        n.edges_len = len as u8;
+        true
     }
 
-    fn selection(&mut self, log_total_count: f64, node: &mut Node, side: Side) -> f64 {
-        node.visited_count += 1;
-        if let Some(r) = node.result {
-            node.win_point += r;
+    /// Runs one playout through `node`, leaving `board` at the position the playout
+    /// ended in, and returns the point of `side`, the side to move at `node`.
+    fn selection(
+        &mut self,
+        log_total_count: f64,
+        node: usize,
+        board: &mut BitBoard,
+        side: Side,
+    ) -> f64 {
+        let n = &mut self.nodes[node];
+        n.visited_count += 1;
+        if n.exact {
+            let r = n.result.point().unwrap_or(DRAW_POINT);
+            n.add_point(r);
             return r;
         }
-        if node.board.is_full() {
-            node.win_point = DRAW_POINT;
+        if board.is_full() {
+            n.exact = true;
+            n.add_point(DRAW_POINT);
             return DRAW_POINT;
         }
-        if node.children.is_empty() {
-            if node.visited_count <= self.expansion_threshold {
-                let r = random_down(&mut self.rng, &node.board, side);
-                node.win_point += r;
+        if self.nodes[node].edges_len == 0 {
+            if self.nodes[node].visited_count <= self.expansion_threshold
+                || !self.expand(node, board, side)
+            {
+                let r = random_down(&mut self.rng, board, side, self.playout);
+                self.nodes[node].add_point(r);
                 return r;
             }
-            let can = node.board.list_can_put();
-            node.children.reserve(can.len());
-            for col in can {
-                let mut board = node.board.clone();
-                if board.put(col, side) {
-                    node.result = Some(WIN_POINT);
-                    node.children = vec![Node::new(board, true)];
-                    node.children[0].visited_count += 1;
-                    node.win_point += WIN_POINT;
-                    return WIN_POINT;
-                } else {
-                    node.children.push(Node::new(board, false));
-                }
+            let first = self.edges[self.nodes[node].first_edge as usize].node as usize;
+            if self.nodes[first].result == Solved::Lose(0) {
+                let n = &mut self.nodes[node];
+                n.result = Solved::Win(1);
+                n.exact = true;
+                n.add_point(WIN_POINT);
+                return WIN_POINT;
             }
         }
         let i = self.choice_child(log_total_count, node);
-        let p = 1.0 - self.selection(log_total_count, &mut node.children[i], side.flip());
-        if node.children.iter().any(|c| c.result == Some(LOSE_POINT)) {
-            node.result = Some(WIN_POINT);
-            node.win_point = node.visited_count as f64;
-        } else if node.children.iter().all(|c| c.result == Some(WIN_POINT)) {
-            node.result = Some(LOSE_POINT);
-            node.win_point = 0.0;
+        let Edge {
+            col, node: child, ..
+        } = self.edges[i];
+        let flipped = self.is_flipped(board);
+        board.put(orient(col as usize, flipped), side);
+        let p = 1.0 - self.selection(log_total_count, child as usize, board, side.flip());
+        // The fastest win through a losing child, or the slowest loss when every
+        // child wins. A win is final once no other child can still win faster:
+        // every child is final or loses for us, or the win takes 3 plies, which a
+        // child not yet proven cannot beat, as it does not end the game and our
+        // next move is 2 plies after it. A loss or a draw is final once every
+        // child is.
+        let mut fastest_win = None;
+        let mut slowest_loss = Some(0);
+        let mut settled = true;
+        let mut all_exact = true;
+        for e in self.edges[self.nodes[node].edges()].iter() {
+            let child = &self.nodes[e.node as usize];
+            match child.result {
+                Solved::Lose(n) => {
+                    fastest_win = Some(fastest_win.map_or(n, |m: u8| m.min(n)));
+                }
+                Solved::Win(n) => slowest_loss = slowest_loss.map(|m: u8| m.max(n)),
+                Solved::Unknown => slowest_loss = None,
+            }
+            settled &= child.exact || matches!(child.result, Solved::Win(_));
+            all_exact &= child.exact;
+        }
+        let n = &mut self.nodes[node];
+        if let Some(plies) = fastest_win {
+            n.result = Solved::Win(plies + 1);
+            n.exact = settled || plies <= 2;
+            n.win_point = n.visited_count as f32;
+            n.square_point = n.visited_count as f32;
+        } else if let Some(plies) = slowest_loss {
+            n.result = Solved::Lose(plies + 1);
+            n.exact = all_exact;
+            n.win_point = 0.0;
+            n.square_point = 0.0;
         } else {
-            node.win_point += p;
+            n.exact = all_exact;
+            n.add_point(p);
         }
         p
     }
 
-    pub fn search(&mut self, board: &BitBoard) -> (usize, f64) {
-        if board.is_full() {
-            return (0, 0.0);
+    /// Starts a new tree at `board`.
+    pub fn reset(&mut self, board: &BitBoard) {
+        self.nodes.clear();
+        self.edges.clear();
+        if let Some(table) = self.transpositions.as_mut() {
+            table.clear();
         }
-        let start = Instant::now();
-        let side = board.calc_next();
-        let mut node = Node::new(board.clone(), false);
-        let mut total_count = 0;
-        while start.elapsed() < self.limit && node.result.is_none() {
-            for _ in 0..1000 {
-                total_count += 1;
-                self.selection((total_count as f64).ln(), &mut node, side);
+        self.root = board.clone();
+        self.total_count = 0;
+        self.child_node(board, Solved::Unknown);
+    }
+
+    /// Position at the root of the tree.
+    pub fn root(&self) -> &BitBoard {
+        &self.root
+    }
+
+    fn is_over(&self) -> bool {
+        self.root.is_full() || self.root.calc_winner().is_some()
+    }
+
+    /// Runs `iterations` playouts from the root, stopping early once it is solved.
+    pub fn run(&mut self, iterations: u32) {
+        if self.is_over() {
+            return;
+        }
+        let side = self.root.calc_next();
+        for _ in 0..iterations {
+            if self.nodes[0].exact {
+                break;
             }
+            self.total_count += 1;
+            let log_total_count = (self.total_count as f64).ln();
+            self.selection(log_total_count, 0, &mut self.root.clone(), side);
         }
-        let best = node
-            .children
+    }
+
+    /// Moves the root to the position after putting in `col`, keeping the
+    /// statistics of its subtree and dropping the rest of the tree.
+    pub fn advance(&mut self, col: usize) {
+        let flipped = self.is_flipped(&self.root);
+        let mut board = self.root.clone();
+        board.put(col, board.calc_next());
+        let child = self.edges[self.nodes[0].edges()]
             .iter()
-            .max_by(|x, y| {
-                use std::cmp::Ordering::*;
-                if x.result == Some(LOSE_POINT) {
-                    Greater
-                } else if y.result == Some(LOSE_POINT) {
-                    Less
-                } else {
-                    x.visited_count.cmp(&y.visited_count)
-                }
-            })
-            .expect("No children");
+            .find(|e| orient(e.col as usize, flipped) == col)
+            .map(|e| e.node);
+        let Some(child) = child else {
+            self.reset(&board);
+            return;
+        };
+        let nodes = std::mem::take(&mut self.nodes);
+        let edges = std::mem::take(&mut self.edges);
+        if let Some(table) = self.transpositions.as_mut() {
+            table.clear();
+        }
+        self.copy_subtree(&nodes, &edges, child, &board, &mut HashMap::new());
+        self.root = board;
+    }
+
+    /// Copies the subtree of `old` at `board` into the arena and returns its index.
+    fn copy_subtree(
+        &mut self,
+        nodes: &[Node],
+        edges: &[Edge],
+        old: u32,
+        board: &BitBoard,
+        copied: &mut HashMap<u32, u32>,
+    ) -> u32 {
+        if let Some(&index) = copied.get(&old) {
+            return index;
+        }
+        let index = self.nodes.len() as u32;
+        copied.insert(old, index);
+        if let Some(table) = self.transpositions.as_mut() {
+            table.insert(canonical(board), index);
+        }
+        let node = &nodes[old as usize];
+        let first = self.edges.len();
+        self.nodes.push(Node {
+            first_edge: first as u32,
+            ..node.clone()
+        });
+        self.edges.extend_from_slice(&edges[node.edges()]);
+        let side = board.calc_next();
+        let flipped = self.is_flipped(board);
+        for i in first..self.edges.len() {
+            let mut board = board.clone();
+            board.put(orient(self.edges[i].col as usize, flipped), side);
+            self.edges[i].node =
+                self.copy_subtree(nodes, edges, self.edges[i].node, &board, copied);
+        }
+        index
+    }
+
+    /// Runs playouts on the current tree for the time limit or until the root is
+    /// solved, and returns how many were run.
+    pub fn grow(&mut self) -> u64 {
+        let start = Instant::now();
+        let total_count = self.total_count;
+        while start.elapsed() < self.limit && !self.is_solved() {
+            self.run(1000);
+        }
+        self.total_count - total_count
+    }
 
-        log(&format!("children={}", node.children.len()));
-        for child in node.children.iter() {
+    /// Runs playouts on the current tree for the time limit and returns the best
+    /// move, or `None` when the game is over.
+    pub fn think(&mut self) -> Option<SearchResult> {
+        if self.is_over() {
+            return None;
+        }
+        let start = Instant::now();
+        let playouts = self.grow();
+        let elapsed = start.elapsed().as_secs_f64();
+        log(&format!(
+            "children={} nodes={} playouts/s={:.0}",
+            self.nodes[0].edges_len,
+            self.nodes.len(),
+            playouts as f64 / elapsed,
+        ));
+        let flipped = self.is_flipped(&self.root);
+        for e in self.edges[self.nodes[0].edges()].iter() {
+            let child = &self.nodes[e.node as usize];
             log(&format!(
-                "visited_count={} win_point={} r={:?}",
+                "col={} visited_count={} win_point={} r={:?}",
+                orient(e.col as usize, flipped),
                 child.visited_count,
-                1.0 - child.win_point / child.visited_count as f64,
+                1.0 - child.win_point as f64 / child.visited_count as f64,
                 child.result,
             ));
         }
+        Some(self.best().expect("No children"))
+    }
+
+    /// The best move so far by [`Outcome::rank`]. `None` before the root is
+    /// expanded.
+    pub fn best(&self) -> Option<SearchResult> {
+        let best = self
+            .root_moves()
+            .into_iter()
+            .max_by_key(|m| m.outcome.rank(m.visits))?;
+        if best.visits == 0 {
+            return None;
+        }
+        Some(SearchResult {
+            col: best.col,
+            point: best.point / best.visits as f64,
+            outcome: best.outcome,
+        })
+    }
+
+    /// Statistics of the children of the root.
+    pub fn root_moves(&self) -> Vec<MoveStats> {
+        let flipped = self.is_flipped(&self.root);
+        self.edges[self.nodes[0].edges()]
+            .iter()
+            .map(|e| {
+                let child = &self.nodes[e.node as usize];
+                MoveStats {
+                    col: orient(e.col as usize, flipped),
+                    visits: child.visited_count,
+                    point: child.visited_count as f64 - child.win_point as f64,
+                    outcome: child.result.mover_outcome(),
+                    exact: child.exact,
+                }
+            })
+            .collect()
+    }
+
+    /// The top `depth` levels of the tree below the root.
+    pub fn export(&self, depth: usize) -> TreeExport {
+        self.export_node(0, None, &self.root, depth)
+    }
+
+    fn export_node(
+        &self,
+        node: u32,
+        col: Option<usize>,
+        board: &BitBoard,
+        depth: usize,
+    ) -> TreeExport {
+        let n = &self.nodes[node as usize];
+        let mut children = Vec::new();
+        if depth > 0 {
+            let flipped = self.is_flipped(board);
+            let side = board.calc_next();
+            for e in self.edges[n.edges()].iter() {
+                let col = orient(e.col as usize, flipped);
+                let mut board = board.clone();
+                board.put(col, side);
+                children.push(self.export_node(e.node, Some(col), &board, depth - 1));
+            }
+            children.sort_by_key(|c| c.col);
+        }
+        TreeExport {
+            col,
+            visits: n.visited_count,
+            win_rate: (n.visited_count > 0)
+                .then(|| 1.0 - n.win_point as f64 / n.visited_count as f64),
+            result: n.result.mover_outcome().describe(),
+            children,
+        }
+    }
 
-        for col in 0..7 {
-            if !board.can_put(col) {
+    /// The top `depth` levels of the tree below the root in Graphviz DOT. Nodes
+    /// shared by transpositions appear once with several incoming edges.
+    pub fn export_dot(&self, depth: usize) -> String {
+        use std::fmt::Write;
+        let mut dot = String::from("digraph mctree {\n    node [shape=box];\n");
+        let mut seen = HashSet::from([0]);
+        let mut queue = VecDeque::from([(0, self.root.clone(), depth)]);
+        self.dot_node(0, &mut dot);
+        // Breadth first, so that a shared node is expanded from its shallowest parent.
+        while let Some((node, board, depth)) = queue.pop_front() {
+            if depth == 0 {
                 continue;
             }
-            let mut board = board.clone();
-            board.put(col, side);
-            if board == best.board {
-                return (col, 1.0 - best.win_point / best.visited_count as f64);
+            let flipped = self.is_flipped(&board);
+            let side = board.calc_next();
+            for e in self.edges[self.nodes[node as usize].edges()].iter() {
+                let col = orient(e.col as usize, flipped);
+                writeln!(dot, "    n{} -> n{} [label=\"{}\"];", node, e.node, col).unwrap();
+                if seen.insert(e.node) {
+                    self.dot_node(e.node, &mut dot);
+                    let mut board = board.clone();
+                    board.put(col, side);
+                    queue.push_back((e.node, board, depth - 1));
+                }
             }
         }
-        (0, 0.0)
+        dot.push_str("}\n");
+        dot
     }
-}
 
-#[cfg(target_arch = "wasm32")]
-fn log(s: &str) {
-    let value = wasm_bindgen::JsValue::from(s);
-    web_sys::console::log_1(&value);
+    fn dot_node(&self, node: u32, dot: &mut String) {
+        use std::fmt::Write;
+        let n = &self.nodes[node as usize];
+        let rate = if n.visited_count > 0 {
+            format!("{:.3}", 1.0 - n.win_point as f64 / n.visited_count as f64)
+        } else {
+            "-".to_owned()
+        };
+        // Colored for the side that moved into the node.
+        let outcome = n.result.mover_outcome();
+        let color = match outcome {
+            Outcome::Unknown => "black",
+            Outcome::Win(_) => "green",
+            Outcome::Loss(_) => "red",
+        };
+        let result = outcome
+            .describe()
+            .map_or_else(String::new, |r| format!("\\n{}", r));
+        writeln!(
+            dot,
+            "    n{} [label=\"N={}\\nW={}{}\", color={}];",
+            node, n.visited_count, rate, result, color
+        )
+        .unwrap();
+    }
+
+    /// Visit count of the child for each column, 0 for columns not in the tree.
+    pub fn visits(&self) -> [u32; 7] {
+        let flipped = self.is_flipped(&self.root);
+        let mut visits = [0; 7];
+        for e in self.edges[self.nodes[0].edges()].iter() {
+            visits[orient(e.col as usize, flipped)] = self.nodes[e.node as usize].visited_count;
+        }
+        visits
+    }
+
+    /// Whether the game at the root is over or its result, with the plies it
+    /// takes, is already known.
+    pub fn is_solved(&self) -> bool {
+        self.nodes[0].exact || self.is_over()
+    }
+
+    /// Searches `board` from a new tree, `None` when the game is over.
+    pub fn search(&mut self, board: &BitBoard) -> Option<SearchResult> {
+        self.reset(board);
+        self.think()
+    }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn log(s: &str) {
-    eprintln!("{}", s);
+/// [`Playout`] policy of [`McTreeAI`] for the generic engine of [`game::mcts`].
+pub struct BoardPlayout<R> {
+    rng: R,
+    playout: Playout,
 }
 
-#[cfg(target_arch = "wasm32")]
-struct Instant(f64);
+impl<R: Rng> BoardPlayout<R> {
+    pub fn new(rng: R, playout: Playout) -> BoardPlayout<R> {
+        BoardPlayout { rng, playout }
+    }
+}
 
-#[cfg(target_arch = "wasm32")]
-impl Instant {
-    fn now() -> Instant {
-        Instant(js_sys::Date::now())
+impl<R: Rng> game::mcts::Playout<BitBoard> for BoardPlayout<R> {
+    fn playout(&mut self, board: &BitBoard) -> GameResult {
+        if let Some(result) = board.result() {
+            return result;
+        }
+        let side = board.calc_next();
+        let point = random_down(&mut self.rng, &mut board.clone(), side, self.playout);
+        if point == WIN_POINT {
+            GameResult::Win(side.into())
+        } else if point == LOSE_POINT {
+            GameResult::Win(side.flip().into())
+        } else {
+            GameResult::Draw
+        }
     }
+}
 
-    fn elapsed(&self) -> Duration {
-        Duration::from_secs_f64((js_sys::Date::now() - self.0) / 1000.0)
+/// Runs the generic engine of [`game::mcts`] for `limit` or until the root is
+/// proven, and returns its best move like [`McTreeAI::think`]. It knows none of
+/// the options of [`McTreeAI`] but the playout policy, which makes it a baseline
+/// for them.
+pub fn generic_search<P: game::mcts::Playout<BitBoard>>(
+    mcts: &mut Mcts<BitBoard, P>,
+    limit: Duration,
+) -> Option<SearchResult> {
+    let start = Instant::now();
+    while start.elapsed() < limit && mcts.proof().is_none() {
+        mcts.run(100);
     }
+    let me = mcts.root().to_move();
+    let best = mcts.best()?;
+    let outcome = match best.proof {
+        Some(proof) if proof.result == GameResult::Win(me) => Outcome::Win(proof.plies + 1),
+        Some(proof) if proof.result == GameResult::Win(me.flip()) => Outcome::Loss(proof.plies + 1),
+        _ => Outcome::Unknown,
+    };
+    Some(SearchResult {
+        col: best.mv,
+        point: best.point,
+        outcome,
+    })
 }
 
+#[cfg(target_arch = "wasm32")]
+fn log(s: &str) {
+    let value = wasm_bindgen::JsValue::from(s);
+    web_sys::console::log_1(&value);
+}
+
+/// The search stats go to the browser console only; native callers such as
+/// bodoge print what they need themselves.
+#[cfg(not(target_arch = "wasm32"))]
+fn log(_: &str) {}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use rand_pcg::Pcg32;
 
+    /// A to move with three in the bottom row, winning at once in column 3.
+    pub(crate) fn take_win_board() -> BitBoard {
+        let mut board = BitBoard::new();
+        for col in [0, 6, 1, 6, 2, 5] {
+            board.put(col, board.calc_next());
+        }
+        board
+    }
+
     #[test]
     fn smoke() {
         let rng = Pcg32::new(1, 11634580027462260723);
         let mut ai = McTreeAI::new(rng, 10, 2, 2.0);
         let mut board = BitBoard::new();
         let mut side = Side::A;
-        while board.calc_winner().is_none() {
-            let (pos, f) = ai.search(&board);
-            assert!(board.can_put(pos));
-            assert!((0.0..=1.0).contains(&f));
-            board.put(pos, side);
+        while board.calc_winner().is_none() && !board.is_full() {
+            let r = ai.search(&board).unwrap();
+            assert!(board.can_put(r.col));
+            assert!((0.0..=1.0).contains(&r.point));
+            board.put(r.col, side);
             side = side.flip();
         }
     }
+
+    /// Plays `games` games between `x` and `y`, alternating the first player, with
+    /// `playouts` playouts a move, and returns the wins, draws and losses of `x`.
+    fn duel<R: Rng>(
+        x: &mut McTreeAI<R>,
+        y: &mut McTreeAI<R>,
+        games: usize,
+        playouts: u32,
+    ) -> [usize; 3] {
+        let mut record = [0; 3];
+        for game in 0..games {
+            let mut board = BitBoard::new();
+            let mut turn = game % 2;
+            let winner = loop {
+                let side = board.calc_next();
+                let ai = if turn == 0 { &mut *x } else { &mut *y };
+                ai.reset(&board);
+                ai.run(playouts);
+                if board.put(ai.best().unwrap().col, side) {
+                    break Some(turn);
+                }
+                if board.is_full() {
+                    break None;
+                }
+                turn = 1 - turn;
+            };
+            match winner {
+                Some(0) => record[0] += 1,
+                None => record[1] += 1,
+                _ => record[2] += 1,
+            }
+        }
+        record
+    }
+
+    #[test]
+    fn transpositions() {
+        let rng = Pcg32::new(1, 11634580027462260723);
+        let mut ai = McTreeAI::new(rng, 10, 2, 2.0).with_transpositions(true);
+        ai.reset(&BitBoard::new());
+        ai.run(3000);
+        let table = ai.transpositions.as_ref().unwrap();
+        assert_eq!(table.len(), ai.nodes.len());
+        // Mirror images share a node, which sums up the visits through both.
+        let child = |col: usize| ai.edges[ai.nodes[0].edges()][col].node;
+        assert_eq!(child(0), child(6));
+        assert_ne!(child(0), child(3));
+        assert_eq!(ai.visits()[0], ai.visits()[6]);
+        // Move orders reaching the same position share a node too.
+        let mut tree = McTreeAI::new(Pcg32::new(1, 11634580027462260723), 10, 2, 2.0);
+        tree.reset(&BitBoard::new());
+        tree.run(3000);
+        assert!(ai.nodes.len() < tree.nodes.len());
+    }
+
+    /// Sharing transpositions wins more than it loses at the same number of
+    /// playouts a move: 62 wins, 6 draws and 32 losses when last measured.
+    ///
+    /// `cargo test --release -p connect-four -- --ignored --nocapture transposition_vs_tree`
+    #[test]
+    #[ignore]
+    fn transposition_vs_tree() {
+        let mut x = McTreeAI::new(Pcg32::new(1, 11634580027462260723), 200, 2, 2.0)
+            .with_transpositions(true);
+        let mut y = McTreeAI::new(Pcg32::new(2, 11634580027462260723), 200, 2, 2.0);
+        let [win, draw, lose] = duel(&mut x, &mut y, 100, 20_000);
+        eprintln!(
+            "transposition vs tree: {} wins, {} draws, {} losses",
+            win, draw, lose
+        );
+        assert!(win > lose + 10);
+    }
+
+    #[test]
+    fn generic_engine() {
+        let mut board = take_win_board();
+        let playout = BoardPlayout::new(Pcg32::new(1, 11634580027462260723), Playout::Tactical);
+        let mut mcts = Mcts::new(&board, playout, 2.0).with_expansion_threshold(2);
+        let r = generic_search(&mut mcts, Duration::from_millis(10)).unwrap();
+        assert_eq!(r.col, 3);
+        assert_eq!(r.outcome, Outcome::Win(1));
+
+        // The opponent must block column 3.
+        board.put(5, board.calc_next());
+        mcts.reset(&board);
+        let r = generic_search(&mut mcts, Duration::from_millis(10)).unwrap();
+        assert_eq!(r.col, 3);
+    }
+
+    /// `cargo test --release -p connect-four -- --ignored --nocapture generic_vs_mctree`
+    #[test]
+    #[ignore]
+    fn generic_vs_mctree() {
+        let mut record = [0; 3];
+        for game in 0..40 {
+            let seed = game as u64;
+            let mut generic = Mcts::new(
+                &BitBoard::new(),
+                BoardPlayout::new(Pcg32::new(seed, 11634580027462260723), Playout::Tactical),
+                2.0,
+            )
+            .with_expansion_threshold(2);
+            let mut ai = McTreeAI::new(Pcg32::new(seed + 100, 11634580027462260723), 200, 2, 2.0)
+                .with_playout(Playout::Tactical);
+            let mut board = BitBoard::new();
+            let mut turn = game % 2;
+            let winner = loop {
+                let side = board.calc_next();
+                let col = if turn == 0 {
+                    generic.reset(&board);
+                    generic_search(&mut generic, Duration::from_millis(200))
+                        .unwrap()
+                        .col
+                } else {
+                    ai.search(&board).unwrap().col
+                };
+                if board.put(col, side) {
+                    break Some(turn);
+                }
+                if board.is_full() {
+                    break None;
+                }
+                turn = 1 - turn;
+            };
+            match winner {
+                Some(0) => record[0] += 1,
+                None => record[1] += 1,
+                _ => record[2] += 1,
+            }
+        }
+        eprintln!(
+            "generic vs mctree: {} wins, {} draws, {} losses",
+            record[0], record[1], record[2]
+        );
+    }
+
+    #[test]
+    fn tactical_playout() {
+        let mut rng = Pcg32::new(1, 11634580027462260723);
+        let board = take_win_board();
+        // A random playout often misses column 3, a tactical one never does.
+        let wins = (0..100)
+            .filter(|_| {
+                random_down(&mut rng, &mut board.clone(), Side::A, Playout::Random) == WIN_POINT
+            })
+            .count();
+        assert!(wins < 90);
+        for _ in 0..100 {
+            assert_eq!(
+                random_down(&mut rng, &mut board.clone(), Side::A, Playout::Tactical),
+                WIN_POINT
+            );
+        }
+        assert_eq!("tactical".parse(), Ok(Playout::Tactical));
+        assert!("greedy".parse::<Playout>().is_err());
+    }
+
+    /// `cargo test --release -p connect-four -- --ignored --nocapture tactical_vs_random`
+    #[test]
+    #[ignore]
+    fn tactical_vs_random() {
+        let mut x = McTreeAI::new(Pcg32::new(1, 11634580027462260723), 200, 2, 2.0)
+            .with_playout(Playout::Tactical);
+        let mut y = McTreeAI::new(Pcg32::new(2, 11634580027462260723), 200, 2, 2.0);
+        let [win, draw, lose] = duel(&mut x, &mut y, 40, 20_000);
+        eprintln!(
+            "tactical vs random playouts: {} wins, {} draws, {} losses",
+            win, draw, lose
+        );
+    }
+
+    #[test]
+    fn selections() {
+        for selection in ["weighted", "ucb1", "ucb1-tuned", "puct"] {
+            let rng = Pcg32::new(1, 11634580027462260723);
+            let mut ai = McTreeAI::new(rng, 10, 2, 2.0).with_selection(selection.parse().unwrap());
+            let r = ai.search(&BitBoard::new()).unwrap();
+            assert!(r.col < 7);
+            assert!((0.0..=1.0).contains(&r.point));
+        }
+        assert!("ucb2".parse::<Selection>().is_err());
+    }
+
+    /// How often `selection` descends to each child of a root visited 100 times,
+    /// in 3000 draws with c = 1. The first child is visited 60 times for a mean of
+    /// 0.62, the second 30 times for 0.5 with the highest prior, and the third 10
+    /// times for 0.35.
+    fn selection_picks(selection: Selection) -> [u32; 3] {
+        let rng = Pcg32::new(1, 11634580027462260723);
+        let mut ai = McTreeAI::new(rng, 10, 2, 1.0).with_selection(selection);
+        let child = |visited_count: u32, mean: f32| Node {
+            visited_count,
+            // Points are of the side to move at the child.
+            win_point: (1.0 - mean) * visited_count as f32,
+            square_point: (1.0 - mean) * visited_count as f32,
+            ..Node::new(Solved::Unknown)
+        };
+        ai.nodes = vec![
+            Node {
+                visited_count: 100,
+                edges_len: 3,
+                ..Node::new(Solved::Unknown)
+            },
+            child(60, 0.62),
+            child(30, 0.5),
+            child(10, 0.35),
+        ];
+        ai.edges = vec![
+            Edge::new(0, 1, 0.1),
+            Edge::new(1, 2, 0.8),
+            Edge::new(2, 3, 0.1),
+        ];
+        let mut picks = [0; 3];
+        for _ in 0..3000 {
+            picks[ai.choice_child(100f64.ln(), 0)] += 1;
+        }
+        picks
+    }
+
+    #[test]
+    fn selection_formulas() {
+        // UCB1: 0.62 + (ln 100 / 60)^0.5 = 0.90, 0.89 and 1.03 for the third.
+        assert_eq!(selection_picks(Selection::Ucb1), [0, 0, 3000]);
+        // UCB1-Tuned caps the variance at 1/4, halving the bonus: 0.76 for the
+        // first, 0.70 and 0.69.
+        assert_eq!(selection_picks(Selection::Ucb1Tuned), [3000, 0, 0]);
+        // PUCT: 0.62 + 0.1 * 100^0.5 / 61 = 0.64, 0.5 + 0.8 * 10 / 31 = 0.76 for
+        // the second, and 0.44.
+        assert_eq!(selection_picks(Selection::Puct), [0, 3000, 0]);
+        // Weighted draws in proportion to the UCB1 values, 31%, 31% and 36%.
+        let picks = selection_picks(Selection::Weighted);
+        let ucb1 = [(0.62, 60.0), (0.5, 30.0), (0.35, 10.0)]
+            .map(|(mean, n): (f64, f64)| mean + (100f64.ln() / n).sqrt());
+        let sum: f64 = ucb1.iter().sum();
+        for (n, value) in picks.into_iter().zip(ucb1) {
+            assert!(
+                (n as f64 / 3000.0 - value / sum).abs() < 0.03,
+                "{:?}",
+                picks
+            );
+        }
+    }
+
+    #[test]
+    fn puct_priors() {
+        let mut board = BitBoard::new();
+        for col in [0, 6, 1, 6, 2] {
+            board.put(col, board.calc_next());
+        }
+        // B has to block column 3.
+        let p = priors(&board, Side::B);
+        assert!((p.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!(p.iter().all(|&x| x <= p[3]));
+        assert!(p[0] < p[1]);
+    }
+
+    /// Every other selection beats the weighted random choice at the same number
+    /// of playouts a move and c = 2: UCB1 by 79 wins, 4 draws and 17 losses,
+    /// UCB1-Tuned by 95-1-4 and PUCT by 94-3-3 when last measured.
+    ///
+    /// `cargo test --release -p connect-four -- --ignored --nocapture selection_vs_weighted`
+    #[test]
+    #[ignore]
+    fn selection_vs_weighted() {
+        for selection in [Selection::Ucb1, Selection::Ucb1Tuned, Selection::Puct] {
+            let mut x = McTreeAI::new(Pcg32::new(1, 11634580027462260723), 200, 2, 2.0)
+                .with_selection(selection);
+            let mut y = McTreeAI::new(Pcg32::new(2, 11634580027462260723), 200, 2, 2.0);
+            let [win, draw, lose] = duel(&mut x, &mut y, 100, 20_000);
+            eprintln!(
+                "{:?} vs weighted: {} wins, {} draws, {} losses",
+                selection, win, draw, lose
+            );
+            assert!(win > lose + 40, "{:?}", selection);
+        }
+    }
+
+    #[test]
+    fn advance() {
+        for transpositions in [false, true] {
+            let rng = Pcg32::new(1, 11634580027462260723);
+            let mut ai = McTreeAI::new(rng, 10, 2, 2.0).with_transpositions(transpositions);
+            ai.reset(&BitBoard::new());
+            ai.run(3000);
+            let nodes = ai.nodes.len();
+            let child = ai.edges[ai.nodes[0].edges()]
+                .iter()
+                .find(|e| e.col == 3)
+                .map(|e| ai.nodes[e.node as usize].visited_count)
+                .unwrap();
+            ai.advance(3);
+            assert!(ai.nodes.len() < nodes);
+            assert_eq!(ai.nodes[0].visited_count, child);
+            assert_eq!(ai.root().calc_next(), Side::B);
+            if let Some(table) = ai.transpositions.as_ref() {
+                assert_eq!(table.len(), ai.nodes.len());
+            }
+            ai.run(1000);
+            let visits = ai.visits();
+            assert!(visits.iter().sum::<u32>() > 1000);
+            assert!(!ai.is_solved());
+            let r = ai.think().unwrap();
+            ai.advance(r.col);
+            assert_eq!(ai.root().calc_next(), Side::A);
+        }
+    }
+
+    #[test]
+    fn export() {
+        for transpositions in [false, true] {
+            let rng = Pcg32::new(1, 11634580027462260723);
+            let mut ai = McTreeAI::new(rng, 10, 2, 2.0).with_transpositions(transpositions);
+            ai.reset(&BitBoard::new());
+            ai.run(2000);
+            let tree = ai.export(2);
+            assert_eq!(tree.col, None);
+            assert_eq!(tree.visits, 2000);
+            let cols: Vec<_> = tree.children.iter().map(|c| c.col).collect();
+            assert_eq!(cols, (0..7).map(Some).collect::<Vec<_>>());
+            assert!(tree.children.iter().all(|c| c.children.len() == 7));
+            assert!(tree.children[0].children[0].children.is_empty());
+            assert!(serde_json::to_string(&tree)
+                .unwrap()
+                .contains("\"winRate\""));
+
+            let dot = ai.export_dot(1);
+            assert!(dot.starts_with("digraph mctree {"));
+            assert!(dot.contains("n0 [label=\"N=2000"));
+            // Mirror images share a node with transpositions.
+            let edges = dot.matches(" -> ").count();
+            assert_eq!(edges, 7);
+            let nodes = dot.matches("[label=\"N=").count();
+            assert_eq!(nodes, if transpositions { 5 } else { 8 });
+        }
+    }
+
+    #[test]
+    fn solved_distance() {
+        let rng = Pcg32::new(1, 11634580027462260723);
+        let mut ai = McTreeAI::new(rng, 1000, 2, 2.0);
+        let mut board = BitBoard::new();
+        for col in [1, 6, 2, 6] {
+            board.put(col, board.calc_next());
+        }
+        // Column 3 makes an open three, and B can block only one end.
+        let r = ai.search(&board).unwrap();
+        assert_eq!((r.col, r.outcome), (3, Outcome::Win(3)));
+        assert_eq!(r.outcome.describe().unwrap(), "win in 3");
+        board.put(3, Side::A);
+        let r = ai.search(&board).unwrap();
+        assert_eq!(r.outcome, Outcome::Loss(2));
+        board.put(0, Side::B);
+        let r = ai.search(&board).unwrap();
+        assert_eq!((r.col, r.outcome), (4, Outcome::Win(1)));
+        board.put(4, Side::A);
+        assert_eq!(ai.search(&board), None);
+    }
+
+    #[test]
+    fn fastest_win() {
+        // Positions where a longer win used to be proven before the fastest.
+        let cases = [
+            (
+                &[
+                    5, 2, 6, 3, 2, 3, 0, 3, 4, 2, 3, 0, 4, 1, 3, 1, 0, 4, 4, 0, 4, 6, 1, 6, 1, 2, 3,
+                ][..],
+                Outcome::Loss(10),
+            ),
+            (
+                &[
+                    1, 4, 6, 3, 5, 6, 1, 0, 6, 1, 6, 0, 0, 4, 6, 6, 4, 3, 3, 1, 0, 3, 3, 3, 5, 5,
+                    1, 4,
+                ],
+                Outcome::Loss(10),
+            ),
+            (
+                &[
+                    1, 0, 3, 6, 3, 0, 3, 0, 2, 3, 0, 6, 3, 4, 6, 2, 1, 2, 3, 4, 1, 0, 2, 6, 6, 1,
+                    2, 6,
+                ],
+                Outcome::Win(7),
+            ),
+        ];
+        for (cols, want) in cases {
+            let mut board = BitBoard::new();
+            for &col in cols {
+                board.put(col, board.calc_next());
+            }
+            let mut ai = McTreeAI::new(Pcg32::new(1, 11634580027462260723), 10, 2, 2.0);
+            ai.reset(&board);
+            ai.run(300_000);
+            assert!(ai.is_solved(), "{:?}", cols);
+            assert_eq!(ai.best().unwrap().outcome, want, "{:?}", cols);
+        }
+    }
+
+    #[test]
+    fn node_cap() {
+        let rng = Pcg32::new(1, 11634580027462260723);
+        let mut ai = McTreeAI::new(rng, 10, 2, 2.0)
+            .with_max_nodes(100)
+            .with_transpositions(true);
+        let r = ai.search(&BitBoard::new()).unwrap();
+        assert!(r.col < 7);
+        assert!(ai.nodes.len() <= 100);
+        assert!(ai.edges.len() <= 100);
+        // As in the doc of DEFAULT_MAX_NODES.
+        assert_eq!(std::mem::size_of::<Node>(), 20);
+        assert_eq!(std::mem::size_of::<Edge>(), 12);
+    }
+
+    #[test]
+    fn take_win() {
+        let rng = Pcg32::new(1, 11634580027462260723);
+        let mut ai = McTreeAI::new(rng, 10, 2, 2.0);
+        let r = ai.search(&take_win_board()).unwrap();
+        assert_eq!((r.col, r.outcome), (3, Outcome::Win(1)));
+    }
 }
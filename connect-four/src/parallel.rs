@@ -0,0 +1,199 @@
+//! Root-parallel MCTS: every thread grows its own tree from the same position
+//! with its own random numbers, and the statistics of the moves from the root are
+//! summed up at the end.
+use std::{cmp::Reverse, thread};
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::{BitBoard, McTreeAI, MoveStats, Outcome};
+
+/// Result of [`search`], shaped like [`SearchResponse`](crate::SearchResponse)
+/// with the merged statistics added.
+#[derive(Debug, Serialize)]
+pub struct ParallelReport {
+    pub position: Option<u32>,
+    pub score: String,
+    pub result: Option<String>,
+    /// Visit count of each column over all the trees.
+    pub visits: [u32; 7],
+    pub playouts: u64,
+    pub threads: usize,
+}
+
+/// Searches `board` on `threads` threads, each with the tree built by `make` from
+/// the index of the thread, for the time limit of the trees.
+pub fn search<R, F>(board: &BitBoard, threads: usize, make: F) -> ParallelReport
+where
+    R: Rng + Send,
+    F: Fn(usize) -> McTreeAI<R> + Sync,
+{
+    let threads = threads.max(1);
+    if board.is_full() || board.calc_winner().is_some() {
+        return ParallelReport {
+            position: None,
+            score: "".to_owned(),
+            result: None,
+            visits: [0; 7],
+            playouts: 0,
+            threads,
+        };
+    }
+    let results: Vec<(Vec<MoveStats>, u64)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let make = &make;
+                scope.spawn(move || {
+                    let mut ai = make(i);
+                    ai.reset(board);
+                    let playouts = ai.grow();
+                    (ai.root_moves(), playouts)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("search thread panicked"))
+            .collect()
+    });
+
+    merge(results, threads)
+}
+
+/// Sums up the root moves of each tree with the playouts it ran.
+fn merge(results: Vec<(Vec<MoveStats>, u64)>, threads: usize) -> ParallelReport {
+    let mut merged: [Option<MoveStats>; 7] = [None; 7];
+    let mut playouts = 0;
+    for (moves, p) in results {
+        playouts += p;
+        for m in moves {
+            let merged = merged[m.col].get_or_insert(MoveStats {
+                col: m.col,
+                visits: 0,
+                point: 0.0,
+                outcome: Outcome::Unknown,
+                exact: false,
+            });
+            merged.visits += m.visits;
+            merged.point += m.point;
+            if better_known(&m, merged) {
+                merged.outcome = m.outcome;
+                merged.exact = m.exact;
+            }
+        }
+    }
+    let visits = merged.map(|m| m.map_or(0, |m| m.visits));
+    let best = merged
+        .iter()
+        .flatten()
+        .max_by_key(|m| m.outcome.rank(m.visits));
+    match best {
+        Some(m) => ParallelReport {
+            position: Some(m.col as u32),
+            score: (m.point / m.visits.max(1) as f64).to_string(),
+            result: m.outcome.describe(),
+            visits,
+            playouts,
+            threads,
+        },
+        None => ParallelReport {
+            position: None,
+            score: "".to_owned(),
+            result: None,
+            visits,
+            playouts,
+            threads,
+        },
+    }
+}
+
+/// Whether the outcome of `m` is closer to the truth than that of `merged`, so
+/// that the merged one does not depend on the order of the threads. A proven
+/// outcome beats an unknown one and an exact one beats one still searched.
+/// Otherwise the fewer plies win, for a loss as for a win, as the plies of an
+/// outcome that is not exact yet only shrink with more search.
+fn better_known(m: &MoveStats, merged: &MoveStats) -> bool {
+    let key = |m: &MoveStats| {
+        let plies = match m.outcome {
+            Outcome::Unknown => u32::MAX,
+            Outcome::Win(n) | Outcome::Loss(n) => n,
+        };
+        (m.outcome != Outcome::Unknown, m.exact, Reverse(plies))
+    };
+    key(m) > key(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mctree::tests::take_win_board;
+    use rand_pcg::Pcg32;
+
+    fn stats(col: usize, outcome: Outcome, exact: bool) -> MoveStats {
+        MoveStats {
+            col,
+            visits: 10,
+            point: 5.0,
+            outcome,
+            exact,
+        }
+    }
+
+    #[test]
+    fn merge_outcomes() {
+        // Two threads that proved the same moves at different distances.
+        let fast = vec![
+            stats(0, Outcome::Win(7), false),
+            stats(1, Outcome::Loss(10), false),
+            stats(2, Outcome::Loss(12), true),
+            stats(3, Outcome::Unknown, false),
+        ];
+        let slow = vec![
+            stats(0, Outcome::Win(9), false),
+            stats(1, Outcome::Loss(12), false),
+            stats(2, Outcome::Loss(8), false),
+            stats(3, Outcome::Loss(20), false),
+        ];
+        for results in [
+            vec![(fast.clone(), 40), (slow.clone(), 40)],
+            vec![(slow.clone(), 40), (fast.clone(), 40)],
+        ] {
+            let report = super::merge(results, 2);
+            assert_eq!(report.position, Some(0));
+            assert_eq!(report.result.as_deref(), Some("win in 7"));
+            assert_eq!(report.visits, [20, 20, 20, 20, 0, 0, 0]);
+        }
+        // The shorter of two bounds, and an exact outcome over any bound.
+        assert!(better_known(&fast[1], &slow[1]));
+        assert!(!better_known(&slow[1], &fast[1]));
+        assert!(better_known(&fast[2], &slow[2]));
+        assert!(!better_known(&slow[2], &fast[2]));
+        assert!(better_known(&slow[3], &fast[3]));
+    }
+
+    #[test]
+    fn merge() {
+        let make = |i| McTreeAI::new(Pcg32::new(i as u64, 11634580027462260723), 10, 2, 2.0);
+        let report = search(&take_win_board(), 4, make);
+        assert_eq!(report.position, Some(3));
+        assert_eq!(report.result.as_deref(), Some("win in 1"));
+        assert_eq!(report.threads, 4);
+        let report = search(&BitBoard::new(), 2, make);
+        assert!(report.position.is_some());
+        assert!(report.playouts > 0);
+        assert!(report.visits.iter().all(|&v| v > 0));
+        // The visits of both trees add up, less the playouts each root took
+        // before being expanded.
+        let total: u32 = report.visits.iter().sum();
+        assert_eq!(total as u64, report.playouts - 2 * 2);
+        assert_eq!(
+            report.position,
+            report
+                .visits
+                .iter()
+                .enumerate()
+                .max_by_key(|&(_, v)| v)
+                .map(|(col, _)| col as u32)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Post-game analysis: replays a game and grades every move by how much of the
+//! expected point it gave away according to an evaluator.
+use serde::Serialize;
+
+use crate::{
+    mcts::{Mcts, Playout},
+    Game, GameResult, Player,
+};
+
+/// What an engine thinks of a position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation<M> {
+    /// Expected point of the player to move, 1 for a win, 0.5 for a draw and 0
+    /// for a loss.
+    pub point: f64,
+    /// The move the engine would play.
+    pub best: Option<M>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Classification {
+    Best,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+/// Least loss of expected point for each grade below [`Classification::Best`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Thresholds {
+    pub inaccuracy: f64,
+    pub mistake: f64,
+    pub blunder: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Thresholds {
+        Thresholds {
+            inaccuracy: 0.05,
+            mistake: 0.1,
+            blunder: 0.2,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn classify(&self, loss: f64) -> Classification {
+        if loss >= self.blunder {
+            Classification::Blunder
+        } else if loss >= self.mistake {
+            Classification::Mistake
+        } else if loss >= self.inaccuracy {
+            Classification::Inaccuracy
+        } else {
+            Classification::Best
+        }
+    }
+}
+
+/// One move of the game with the expected point of its player before and after.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Annotation<M> {
+    pub ply: usize,
+    pub player: Player,
+    #[serde(rename = "move")]
+    pub mv: M,
+    pub before: f64,
+    pub after: f64,
+    /// The move preferred by the engine.
+    pub best: Option<M>,
+    pub classification: Classification,
+}
+
+/// Point of a finished game for `player`.
+pub fn result_point(result: GameResult, player: Player) -> f64 {
+    (result.score(player) + 1) as f64 / 2.0
+}
+
+/// [`Evaluation`] from a search tree, exact once the root is proven.
+pub fn mcts_evaluation<G: Game, P: Playout<G>>(mcts: &Mcts<G, P>) -> Evaluation<G::Move> {
+    let me = mcts.root().to_move();
+    match mcts.best() {
+        Some(best) => Evaluation {
+            point: best
+                .proof
+                .map_or(best.point, |proof| result_point(proof.result, me)),
+            best: Some(best.mv),
+        },
+        None => Evaluation {
+            point: mcts
+                .proof()
+                .map_or(0.5, |proof| result_point(proof.result, me)),
+            best: None,
+        },
+    }
+}
+
+/// Replays `moves` from `start`, evaluating every position once with
+/// `evaluate`. A move the engine prefers is always [`Classification::Best`];
+/// others are graded by the point lost, as the engine may see more after the
+/// move than before it.
+pub fn analyze<G: Game>(
+    start: &G,
+    moves: &[G::Move],
+    mut evaluate: impl FnMut(&G) -> Evaluation<G::Move>,
+    thresholds: &Thresholds,
+) -> Result<Vec<Annotation<G::Move>>, String> {
+    let mut positions = vec![start.clone()];
+    for (ply, &mv) in moves.iter().enumerate() {
+        let mut next = positions[ply].clone();
+        if !next.legal_moves().contains(&mv) {
+            return Err(format!("illegal move {:?} at ply {}", mv, ply));
+        }
+        next.apply(mv);
+        positions.push(next);
+    }
+    let evaluations = positions
+        .iter()
+        .map(|p| p.result().is_none().then(|| evaluate(p)))
+        .collect::<Vec<_>>();
+    let point_for = |i: usize, player: Player| match (&evaluations[i], positions[i].result()) {
+        (_, Some(result)) => result_point(result, player),
+        (Some(e), None) if positions[i].to_move() == player => e.point,
+        (Some(e), None) => 1.0 - e.point,
+        (None, None) => unreachable!(),
+    };
+    Ok(moves
+        .iter()
+        .enumerate()
+        .map(|(ply, &mv)| {
+            let player = positions[ply].to_move();
+            let before = point_for(ply, player);
+            let after = point_for(ply + 1, player);
+            let best = evaluations[ply].as_ref().and_then(|e| e.best);
+            let classification = if best == Some(mv) {
+                Classification::Best
+            } else {
+                thresholds.classify(before - after)
+            };
+            Annotation {
+                ply,
+                player,
+                mv,
+                before,
+                after,
+                best,
+                classification,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mcts::RandomPlayout, testing::Nim};
+
+    /// Piles that are multiples of 3 are lost for the player to move.
+    fn perfect(nim: &Nim) -> Evaluation<u32> {
+        let r = nim.pile % 3;
+        Evaluation {
+            point: if r == 0 { 0.0 } else { 1.0 },
+            best: Some(if r == 0 { 1 } else { r }),
+        }
+    }
+
+    #[test]
+    fn grades() {
+        let nim = Nim::new(7);
+        let annotations = analyze(&nim, &[2, 2, 2, 1], perfect, &Thresholds::default()).unwrap();
+        let classes = annotations
+            .iter()
+            .map(|a| a.classification)
+            .collect::<Vec<_>>();
+        use Classification::*;
+        // 7 -> 5 throws the win away; the loser's 3 -> 1 loses nothing more.
+        assert_eq!(classes, vec![Blunder, Best, Best, Best]);
+        assert_eq!(annotations[0].best, Some(1));
+        assert_eq!(annotations[0].before, 1.0);
+        assert_eq!(annotations[0].after, 0.0);
+        assert_eq!(annotations[2].best, Some(1));
+        assert_eq!(annotations[3].after, 1.0);
+        assert_eq!(annotations[3].player, Player::Second);
+
+        assert!(analyze(&nim, &[3], perfect, &Thresholds::default()).is_err());
+        let t = Thresholds::default();
+        assert_eq!(t.classify(0.07), Inaccuracy);
+        assert_eq!(t.classify(0.15), Mistake);
+        assert_eq!(t.classify(0.01), Best);
+    }
+
+    #[test]
+    fn mcts() {
+        let nim = Nim::new(4);
+        let mut mcts = Mcts::new(&nim, RandomPlayout::new(1), 1.4);
+        mcts.run(1000);
+        let e = mcts_evaluation(&mcts);
+        assert_eq!(
+            e,
+            Evaluation {
+                point: 1.0,
+                best: Some(1)
+            }
+        );
+    }
+}
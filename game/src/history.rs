@@ -0,0 +1,221 @@
+//! A game with its moves, which can be undone, redone and jumped through while
+//! the position and everything derived from it stay consistent.
+use serde::Serialize;
+
+use crate::{record::Outcome, Game, Player};
+
+/// Positions after each move from the start. Undone moves are kept for redo
+/// until a different move is played.
+#[derive(Debug, Clone)]
+pub struct History<G: Game> {
+    /// `states[i]` is the position after `moves[..i]`.
+    states: Vec<G>,
+    moves: Vec<G::Move>,
+    ply: usize,
+}
+
+/// What a frontend shows of a [`History`] besides the board.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status<M> {
+    pub ply: usize,
+    /// Plies up to the last move that can be redone.
+    pub length: usize,
+    pub to_move: Player,
+    pub result: Option<Outcome>,
+    pub legal_moves: Vec<M>,
+    /// The moves to the current position.
+    pub moves: Vec<M>,
+    /// [`Game::hash_key`] in hex, as it does not fit a JS number.
+    pub hash: String,
+}
+
+impl<G: Game> History<G> {
+    pub fn new(start: G) -> History<G> {
+        History {
+            states: vec![start],
+            moves: Vec::new(),
+            ply: 0,
+        }
+    }
+
+    pub fn current(&self) -> &G {
+        &self.states[self.ply]
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    /// Plies up to the last move that can be redone.
+    pub fn length(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// The moves to the current position.
+    pub fn moves(&self) -> &[G::Move] {
+        &self.moves[..self.ply]
+    }
+
+    /// Plays `mv` from the current position. The undone moves are dropped unless
+    /// `mv` is the next of them.
+    pub fn play(&mut self, mv: G::Move) -> Result<(), String> {
+        if self.moves.get(self.ply) == Some(&mv) {
+            self.ply += 1;
+            return Ok(());
+        }
+        let game = self.current();
+        if !game.legal_moves().contains(&mv) {
+            return Err(format!("illegal move {:?} at ply {}", mv, self.ply));
+        }
+        let mut next = game.clone();
+        next.apply(mv);
+        self.states.truncate(self.ply + 1);
+        self.moves.truncate(self.ply);
+        self.states.push(next);
+        self.moves.push(mv);
+        self.ply += 1;
+        Ok(())
+    }
+
+    /// Takes back the last move, if any.
+    pub fn undo(&mut self) -> bool {
+        if self.ply == 0 {
+            return false;
+        }
+        self.ply -= 1;
+        true
+    }
+
+    /// Plays the last undone move again, if any.
+    pub fn redo(&mut self) -> bool {
+        if self.ply == self.moves.len() {
+            return false;
+        }
+        self.ply += 1;
+        true
+    }
+
+    /// Goes to the position after `ply` moves, keeping the moves after it for
+    /// redo.
+    pub fn jump_to(&mut self, ply: usize) -> Result<(), String> {
+        if ply > self.moves.len() {
+            return Err(format!(
+                "ply {} is past the last move {}",
+                ply,
+                self.moves.len()
+            ));
+        }
+        self.ply = ply;
+        Ok(())
+    }
+
+    pub fn status(&self) -> Status<G::Move> {
+        let game = self.current();
+        Status {
+            ply: self.ply,
+            length: self.moves.len(),
+            to_move: game.to_move(),
+            result: game.result().map(Outcome::from),
+            legal_moves: game.legal_moves(),
+            moves: self.moves().to_vec(),
+            hash: format!("{:016x}", game.hash_key()),
+        }
+    }
+}
+
+impl<G: Game + Default> Default for History<G> {
+    fn default() -> History<G> {
+        History::new(G::default())
+    }
+}
+
+/// Exposes `play`, `undo`, `redo`, `jumpTo` and `status` of the `history: History<G>`
+/// field of a wasm-bindgen struct, for games whose moves are `usize`. The struct
+/// keeps its own constructor and `board`, whose forms differ by game. The crate
+/// using it depends on wasm-bindgen and serde-wasm-bindgen.
+///
+/// ```ignore
+/// game::history_methods! {
+///     GameHistory,
+///     /// Puts a disc in `col` for the side to move.
+///     play(col)
+/// }
+/// ```
+#[macro_export]
+macro_rules! history_methods {
+    ($name:ident, $(#[$doc:meta])* play($mv:ident)) => {
+        #[::wasm_bindgen::prelude::wasm_bindgen]
+        impl $name {
+            $(#[$doc])*
+            pub fn play(&mut self, $mv: u32) -> Result<(), ::wasm_bindgen::JsValue> {
+                self.history
+                    .play($mv as usize)
+                    .map_err(|e| ::wasm_bindgen::JsValue::from_str(&e))
+            }
+
+            pub fn undo(&mut self) -> bool {
+                self.history.undo()
+            }
+
+            pub fn redo(&mut self) -> bool {
+                self.history.redo()
+            }
+
+            #[wasm_bindgen(js_name = jumpTo)]
+            pub fn jump_to(&mut self, ply: u32) -> Result<(), ::wasm_bindgen::JsValue> {
+                self.history
+                    .jump_to(ply as usize)
+                    .map_err(|e| ::wasm_bindgen::JsValue::from_str(&e))
+            }
+
+            /// `ply`, `length`, `toMove`, `result`, `legalMoves`, `moves` and `hash`
+            /// of the current position.
+            pub fn status(&self) -> Result<::wasm_bindgen::JsValue, ::wasm_bindgen::JsValue> {
+                Ok(::serde_wasm_bindgen::to_value(&self.history.status())?)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    #[test]
+    fn undo_redo() {
+        let start = Nim::new(5);
+        let mut history = History::new(start.clone());
+        assert!(!history.undo());
+        history.play(2).unwrap();
+        history.play(1).unwrap();
+        assert!(history.play(3).is_err());
+        let after = history.status();
+        assert_eq!(after.moves, vec![2, 1]);
+        assert_eq!(after.to_move, Player::First);
+
+        assert!(history.undo());
+        assert!(history.undo());
+        let status = history.status();
+        assert_eq!((status.ply, status.length), (0, 2));
+        assert_eq!(status.hash, format!("{:016x}", start.hash_key()));
+        assert!(history.redo());
+        assert!(history.redo());
+        assert!(!history.redo());
+        assert_eq!(history.status(), after);
+
+        history.jump_to(1).unwrap();
+        assert!(history.jump_to(3).is_err());
+        // Playing the undone move keeps the rest for redo; another move drops it.
+        history.play(1).unwrap();
+        assert_eq!(history.length(), 2);
+        history.undo();
+        history.play(2).unwrap();
+        assert_eq!(history.moves(), &[2, 2]);
+        assert_eq!(history.length(), 2);
+        history.play(1).unwrap();
+        assert_eq!(history.status().result, Some(Outcome::First));
+        assert!(history.status().legal_moves.is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! Common interface of the board games in this workspace, so that searchers and
+//! tools can be written once for all of them.
+pub mod analysis;
+pub mod history;
+pub mod mcts;
+pub mod negamax;
+pub mod puzzle;
+pub mod record;
+pub mod time;
+#[cfg(test)]
+pub(crate) mod testing;
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// One of the two players, in the order they move first.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Player {
+    First,
+    Second,
+}
+
+impl Player {
+    #[inline]
+    pub fn flip(self) -> Player {
+        match self {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        }
+    }
+
+    #[inline]
+    pub fn index(self) -> usize {
+        match self {
+            Player::First => 0,
+            Player::Second => 1,
+        }
+    }
+}
+
+/// How a finished game ended.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GameResult {
+    Win(Player),
+    Draw,
+}
+
+impl GameResult {
+    /// 1 for a win of `player`, 0 for a draw and -1 for a loss.
+    pub fn score(self, player: Player) -> i32 {
+        match self {
+            GameResult::Win(p) if p == player => 1,
+            GameResult::Win(_) => -1,
+            GameResult::Draw => 0,
+        }
+    }
+}
+
+/// A position of a two-player game with perfect information.
+///
+/// The player to move is part of the state and may stay the same after a move,
+/// as with the extra turns of mancala.
+pub trait Game: Clone {
+    type Move: Copy + Eq + Hash + Debug;
+
+    fn to_move(&self) -> Player;
+
+    /// Moves of the player to move, empty once the game is over.
+    fn legal_moves(&self) -> Vec<Self::Move>;
+
+    /// Plays `mv`, which must be legal.
+    fn apply(&mut self, mv: Self::Move);
+
+    /// `None` while the game goes on.
+    fn result(&self) -> Option<GameResult>;
+
+    /// A hash of the position for transposition tables. Equal positions have equal
+    /// keys within one process.
+    fn hash_key(&self) -> u64;
+}
+
+/// [`Game::hash_key`] of a position that implements [`Hash`].
+pub fn hash_of<T: Hash>(state: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Number of move sequences of length `depth` from `game`, counting a finished
+/// game as one leaf. Checks move generation against known counts.
+pub fn perft<G: Game>(game: &G, depth: usize) -> u64 {
+    if depth == 0 || game.result().is_some() {
+        return 1;
+    }
+    game.legal_moves()
+        .into_iter()
+        .map(|mv| {
+            let mut next = game.clone();
+            next.apply(mv);
+            perft(&next, depth - 1)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    #[test]
+    fn perft_nim() {
+        let nim = Nim::new(4);
+        // Compositions of 4 into parts 1 and 2.
+        assert_eq!(perft(&nim, 4), 5);
+        assert_eq!(perft(&nim, 1), 2);
+        assert_eq!(nim.hash_key(), nim.clone().hash_key());
+    }
+
+    #[test]
+    fn result_score() {
+        let win = GameResult::Win(Player::Second);
+        assert_eq!(win.score(Player::Second), 1);
+        assert_eq!(win.score(Player::First), -1);
+        assert_eq!(GameResult::Draw.score(Player::First), 0);
+        assert_eq!(Player::First.flip().index(), 1);
+    }
+}
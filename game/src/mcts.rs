@@ -0,0 +1,623 @@
+//! Monte Carlo tree search over any [`Game`], with UCT selection, a solver that
+//! proves won, lost and drawn positions, and a pluggable playout policy.
+//!
+//! Every node remembers the player who moved into it, so moves that keep the same
+//! player to move, like the extra turns of mancala, need no special care.
+use std::cmp::Ordering;
+
+use crate::{Game, GameResult, Player};
+
+pub const DEFAULT_MAX_NODES: usize = 1 << 20;
+
+/// Plays a position to the end.
+pub trait Playout<G: Game> {
+    fn playout(&mut self, game: &G) -> GameResult;
+}
+
+/// Plies after which [`RandomPlayout`] calls a game that has not ended a draw.
+pub const DEFAULT_MAX_PLAYOUT_PLIES: u32 = 1000;
+
+/// Uniformly random moves, drawn from a SplitMix64 generator so that the engine
+/// does not depend on any version of `rand`.
+#[derive(Debug, Clone)]
+pub struct RandomPlayout {
+    state: u64,
+    max_plies: u32,
+}
+
+impl RandomPlayout {
+    pub fn new(seed: u64) -> RandomPlayout {
+        RandomPlayout {
+            state: seed,
+            max_plies: DEFAULT_MAX_PLAYOUT_PLIES,
+        }
+    }
+
+    /// Calls a game a draw once `max_plies` random moves have not ended it, as
+    /// those of games with cycles may never end.
+    pub fn with_max_plies(mut self, max_plies: u32) -> RandomPlayout {
+        self.max_plies = max_plies;
+        self
+    }
+
+    /// A uniform index below `n`.
+    fn below(&mut self, n: usize) -> usize {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        ((z as u128 * n as u128) >> 64) as usize
+    }
+}
+
+impl<G: Game> Playout<G> for RandomPlayout {
+    fn playout(&mut self, game: &G) -> GameResult {
+        let mut game = game.clone();
+        for _ in 0..self.max_plies {
+            if let Some(result) = game.result() {
+                return result;
+            }
+            let moves = game.legal_moves();
+            game.apply(moves[self.below(moves.len())]);
+        }
+        game.result().unwrap_or(GameResult::Draw)
+    }
+}
+
+/// The result of a position under perfect play.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Proof {
+    pub result: GameResult,
+    /// Plies to the end of the game along the lines proven so far, where the
+    /// winner takes the fastest win and the loser the slowest loss. It can only
+    /// shrink while the search goes on, and is as short as possible for the
+    /// winner and as long as possible for the loser once [`Mcts::proof`] returns
+    /// the proof of the root.
+    pub plies: u32,
+}
+
+impl Proof {
+    /// Larger is better for `player`: a faster win, a draw, a slower loss.
+    fn rank(self, player: Player) -> (i32, i64) {
+        match self.result.score(player) {
+            1 => (1, -(self.plies as i64)),
+            0 => (0, 0),
+            _ => (-1, self.plies as i64),
+        }
+    }
+
+    fn cmp_for(self, other: Proof, player: Player) -> Ordering {
+        self.rank(player).cmp(&other.rank(player))
+    }
+}
+
+/// Search statistics of a move from the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveStats<M> {
+    pub mv: M,
+    pub visits: u32,
+    /// Mean point of the move for the player making it, 1 for a win, 0.5 for a
+    /// draw and 0 for a loss.
+    pub point: f64,
+    /// Result after the move, once proven. Its plies may still shrink until the
+    /// root is.
+    pub proof: Option<Proof>,
+}
+
+impl<M> MoveStats<M> {
+    /// Orders moves by proven wins, fastest first, then unproven or drawn moves by
+    /// visits, then proven losses, slowest first.
+    fn rank(&self, player: Player) -> (u8, i64) {
+        match self.proof {
+            Some(proof) if proof.result == GameResult::Win(player) => (2, -(proof.plies as i64)),
+            Some(proof) if proof.result == GameResult::Win(player.flip()) => {
+                (0, proof.plies as i64)
+            }
+            _ => (1, self.visits as i64),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node<G: Game> {
+    game: G,
+    /// The move into this node, `None` at the root.
+    mv: Option<G::Move>,
+    /// The player who made `mv`.
+    mover: Player,
+    visits: u32,
+    /// Accumulated point of `mover`.
+    point: f64,
+    first_child: u32,
+    children_len: u32,
+    proof: Option<Proof>,
+    /// Whether `proof` has its final number of plies. A won node keeps being
+    /// searched until no other move can win faster.
+    exact: bool,
+}
+
+impl<G: Game> Node<G> {
+    fn new(game: G, mv: Option<G::Move>, mover: Player) -> Node<G> {
+        let proof = game.result().map(|result| Proof { result, plies: 0 });
+        Node {
+            game,
+            mv,
+            mover,
+            visits: 0,
+            point: 0.0,
+            first_child: 0,
+            children_len: 0,
+            exact: proof.is_some(),
+            proof,
+        }
+    }
+
+    fn children(&self) -> std::ops::Range<usize> {
+        let first = self.first_child as usize;
+        first..first + self.children_len as usize
+    }
+
+    /// Mean point of `mover`, exact once proven.
+    fn mean(&self) -> f64 {
+        match self.proof {
+            Some(proof) => point(proof.result, self.mover),
+            None => self.point / self.visits as f64,
+        }
+    }
+
+    fn stats(&self) -> MoveStats<G::Move> {
+        MoveStats {
+            mv: self.mv.expect("root has no move"),
+            visits: self.visits,
+            point: if self.visits == 0 && self.proof.is_none() {
+                0.0
+            } else {
+                self.mean()
+            },
+            proof: self.proof,
+        }
+    }
+}
+
+fn point(result: GameResult, player: Player) -> f64 {
+    (result.score(player) + 1) as f64 / 2.0
+}
+
+/// UCT search tree rooted at one position.
+///
+/// Nodes live in one vector with the children of a node next to each other, and
+/// a leaf is expanded once it has been visited `expansion_threshold` times.
+pub struct Mcts<G: Game, P> {
+    playout: P,
+    c: f64,
+    expansion_threshold: u32,
+    max_nodes: usize,
+    nodes: Vec<Node<G>>,
+}
+
+impl<G: Game, P: Playout<G>> Mcts<G, P> {
+    pub fn new(game: &G, playout: P, c: f64) -> Mcts<G, P> {
+        let mut mcts = Mcts {
+            playout,
+            c,
+            expansion_threshold: 1,
+            max_nodes: DEFAULT_MAX_NODES,
+            nodes: Vec::new(),
+        };
+        mcts.reset(game);
+        mcts
+    }
+
+    pub fn with_expansion_threshold(mut self, expansion_threshold: u32) -> Mcts<G, P> {
+        self.expansion_threshold = expansion_threshold;
+        self
+    }
+
+    /// Stops expanding leaves once the tree has `max_nodes` nodes.
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Mcts<G, P> {
+        self.max_nodes = max_nodes.max(1);
+        self
+    }
+
+    /// Drops the tree and starts a new one at `game`.
+    pub fn reset(&mut self, game: &G) {
+        self.nodes.clear();
+        self.nodes
+            .push(Node::new(game.clone(), None, game.to_move().flip()));
+    }
+
+    pub fn root(&self) -> &G {
+        &self.nodes[0].game
+    }
+
+    /// Playouts through the root so far.
+    pub fn visits(&self) -> u32 {
+        self.nodes[0].visits
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Result of the root under perfect play, once proven along with its number
+    /// of plies.
+    pub fn proof(&self) -> Option<Proof> {
+        let root = &self.nodes[0];
+        root.proof.filter(|_| root.exact)
+    }
+
+    /// Runs up to `iterations` playouts, stopping early once the root is proven.
+    pub fn run(&mut self, iterations: u32) {
+        for _ in 0..iterations {
+            if self.nodes[0].exact {
+                break;
+            }
+            self.iterate();
+        }
+    }
+
+    fn iterate(&mut self) {
+        let mut path = vec![0];
+        let mut index = 0;
+        let result = loop {
+            let node = &self.nodes[index];
+            if let Some(proof) = node.proof.filter(|_| node.exact || node.children_len == 0) {
+                break proof.result;
+            }
+            if node.children_len == 0 {
+                if node.visits < self.expansion_threshold || self.nodes.len() >= self.max_nodes {
+                    break self.playout.playout(&node.game);
+                }
+                self.expand(index);
+            }
+            index = self.choose_child(index);
+            path.push(index);
+        };
+        for &i in &path {
+            let node = &mut self.nodes[i];
+            node.visits += 1;
+            node.point += point(result, node.mover);
+        }
+        for &i in path.iter().rev().skip(1) {
+            if !self.prove(i) {
+                break;
+            }
+        }
+    }
+
+    fn expand(&mut self, index: usize) {
+        let game = &self.nodes[index].game;
+        let mover = game.to_move();
+        let children = game
+            .legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut next = game.clone();
+                next.apply(mv);
+                Node::new(next, Some(mv), mover)
+            })
+            .collect::<Vec<_>>();
+        let first_child = self.nodes.len() as u32;
+        let node = &mut self.nodes[index];
+        node.first_child = first_child;
+        node.children_len = children.len() as u32;
+        self.nodes.extend(children);
+    }
+
+    /// The child with the highest UCB1, an unvisited one first. Below a proven
+    /// node, only children whose proof may still change.
+    fn choose_child(&self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        let proven = node.proof.is_some();
+        let log_visits = (node.visits.max(1) as f64).ln();
+        let mut best = (f64::MIN, node.first_child as usize);
+        for i in node.children() {
+            let child = &self.nodes[i];
+            if proven && child.exact {
+                continue;
+            }
+            if child.visits == 0 {
+                return i;
+            }
+            let ucb = child.mean() + self.c * (log_visits / child.visits as f64).sqrt();
+            if ucb > best.0 {
+                best = (ucb, i);
+            }
+        }
+        best.1
+    }
+
+    /// Proves the node at `index` from its children: a win as soon as one move
+    /// wins, otherwise the best result once every move is proven. Returns whether
+    /// the proof of the node changed.
+    ///
+    /// A win is exact once every other move is exact or cannot win, or once it
+    /// takes at most 2 plies, as a move that cannot be proven yet does not end the
+    /// game and needs at least 2. A loss is exact once every move is, and a draw
+    /// as soon as it is proven.
+    fn prove(&mut self, index: usize) -> bool {
+        let node = &self.nodes[index];
+        if node.exact || node.children_len == 0 {
+            return false;
+        }
+        let me = node.game.to_move();
+        let mut best: Option<Proof> = None;
+        let mut all = true;
+        let mut all_exact = true;
+        let mut settled = true;
+        for i in node.children() {
+            let child = &self.nodes[i];
+            match child.proof {
+                Some(proof) => {
+                    if best.is_none_or(|b| proof.cmp_for(b, me) == Ordering::Greater) {
+                        best = Some(proof);
+                    }
+                    settled &= child.exact || proof.result != GameResult::Win(me);
+                }
+                None => {
+                    all = false;
+                    settled = false;
+                }
+            }
+            all_exact &= child.exact;
+        }
+        let proof = match best {
+            Some(best) if all || best.result == GameResult::Win(me) => Proof {
+                result: best.result,
+                plies: best.plies + 1,
+            },
+            _ => return false,
+        };
+        let exact = match proof.result {
+            GameResult::Win(p) if p == me => settled || proof.plies <= 2,
+            GameResult::Win(_) => all_exact,
+            GameResult::Draw => true,
+        };
+        let node = &mut self.nodes[index];
+        if node.proof == Some(proof) && node.exact == exact {
+            return false;
+        }
+        node.proof = Some(proof);
+        node.exact = exact;
+        true
+    }
+
+    /// Statistics of every move from the root, empty until the root is expanded.
+    pub fn root_moves(&self) -> Vec<MoveStats<G::Move>> {
+        self.nodes[0]
+            .children()
+            .map(|i| self.nodes[i].stats())
+            .collect()
+    }
+
+    /// The move to play: the fastest proven win, else the most visited move that
+    /// is not a proven loss, else the slowest loss.
+    pub fn best(&self) -> Option<MoveStats<G::Move>> {
+        let me = self.root().to_move();
+        self.root_moves()
+            .into_iter()
+            .max_by_key(|stats| stats.rank(me))
+    }
+
+    /// Re-roots the tree at the position after `mv`, keeping the subtree already
+    /// searched below it.
+    pub fn advance(&mut self, mv: G::Move) {
+        let Some(child) = self.nodes[0]
+            .children()
+            .find(|&i| self.nodes[i].mv == Some(mv))
+        else {
+            let mut game = self.root().clone();
+            game.apply(mv);
+            self.reset(&game);
+            return;
+        };
+        let old = std::mem::take(&mut self.nodes);
+        let mut root = old[child].clone();
+        root.mv = None;
+        self.nodes.push(root);
+        let mut queue = std::collections::VecDeque::from([(0, child)]);
+        while let Some((new, index)) = queue.pop_front() {
+            let range = old[index].children();
+            self.nodes[new].first_child = self.nodes.len() as u32;
+            for i in range {
+                queue.push_back((self.nodes.len(), i));
+                self.nodes.push(old[i].clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    /// Exact result and plies by plain minimax.
+    fn solve(game: &Nim) -> Proof {
+        if let Some(result) = game.result() {
+            return Proof { result, plies: 0 };
+        }
+        let me = game.to_move();
+        let best = game
+            .legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut next = game.clone();
+                next.apply(mv);
+                solve(&next)
+            })
+            .max_by(|x, y| x.cmp_for(*y, me))
+            .unwrap();
+        Proof {
+            result: best.result,
+            plies: best.plies + 1,
+        }
+    }
+
+    #[test]
+    fn solves_with_extra_turns() {
+        for pile in 1..=12 {
+            let game = Nim::with_extra_turns(pile);
+            let mut mcts = Mcts::new(&game, RandomPlayout::new(pile as u64), 1.4);
+            mcts.run(100_000);
+            let proof = mcts.proof().expect("small games are solved");
+            assert_eq!(proof, solve(&game), "pile {}", pile);
+            let best = mcts.best().unwrap();
+            let mut next = game.clone();
+            next.apply(best.mv);
+            assert_eq!(best.proof, Some(solve(&next)), "pile {}", pile);
+        }
+    }
+
+    const FIRST_WINS: u32 = 100;
+    const SECOND_WINS: u32 = 101;
+
+    /// First wins in 5 plies down a forced line, or in 3 plies by moving to
+    /// where each of 8 replies leaves First 8 moves, only one of which wins.
+    #[derive(Debug, Clone, Hash)]
+    struct Lines {
+        at: u32,
+        to_move: Player,
+    }
+
+    impl Game for Lines {
+        type Move = u32;
+
+        fn to_move(&self) -> Player {
+            self.to_move
+        }
+
+        fn legal_moves(&self) -> Vec<u32> {
+            match self.at {
+                0 => vec![0, 1],
+                1..=4 => vec![0],
+                10..=18 => (0..8).collect(),
+                _ => Vec::new(),
+            }
+        }
+
+        fn apply(&mut self, mv: u32) {
+            self.at = match self.at {
+                0 => [1, 10][mv as usize],
+                1..=3 => self.at + 1,
+                4 => FIRST_WINS,
+                10 => 11 + mv,
+                at if mv == at - 11 => FIRST_WINS,
+                _ => SECOND_WINS,
+            };
+            self.to_move = self.to_move.flip();
+        }
+
+        fn result(&self) -> Option<GameResult> {
+            match self.at {
+                FIRST_WINS => Some(GameResult::Win(Player::First)),
+                SECOND_WINS => Some(GameResult::Win(Player::Second)),
+                _ => None,
+            }
+        }
+
+        fn hash_key(&self) -> u64 {
+            crate::hash_of(self)
+        }
+    }
+
+    /// Two players passing the turn forever.
+    #[derive(Debug, Clone, Hash)]
+    struct Endless {
+        to_move: Player,
+    }
+
+    impl Game for Endless {
+        type Move = u32;
+
+        fn to_move(&self) -> Player {
+            self.to_move
+        }
+
+        fn legal_moves(&self) -> Vec<u32> {
+            vec![0]
+        }
+
+        fn apply(&mut self, _: u32) {
+            self.to_move = self.to_move.flip();
+        }
+
+        fn result(&self) -> Option<GameResult> {
+            None
+        }
+
+        fn hash_key(&self) -> u64 {
+            crate::hash_of(self)
+        }
+    }
+
+    #[test]
+    fn endless_playout() {
+        let game = Endless {
+            to_move: Player::First,
+        };
+        let mut playout = RandomPlayout::new(1).with_max_plies(10);
+        assert_eq!(playout.playout(&game), GameResult::Draw);
+        let mut mcts = Mcts::new(&game, RandomPlayout::new(1), 1.4);
+        mcts.run(100);
+        assert_eq!(mcts.best().unwrap().mv, 0);
+        assert_eq!(mcts.proof(), None);
+    }
+
+    #[test]
+    fn fastest_win() {
+        // The forced line is proven long before the other, and the search must
+        // go on to find the faster win.
+        let game = Lines {
+            at: 0,
+            to_move: Player::First,
+        };
+        for seed in 0..10 {
+            let mut mcts = Mcts::new(&game, RandomPlayout::new(seed), 1.4);
+            mcts.run(10_000);
+            assert_eq!(
+                mcts.proof(),
+                Some(Proof {
+                    result: GameResult::Win(Player::First),
+                    plies: 3
+                }),
+                "seed {}",
+                seed
+            );
+            assert_eq!(mcts.best().unwrap().mv, 1);
+        }
+    }
+
+    #[test]
+    fn proof_plies() {
+        // Taking 2 from 2 wins at once, taking 1 loses a ply later.
+        let mut mcts = Mcts::new(&Nim::with_extra_turns(2), RandomPlayout::new(1), 1.4);
+        mcts.run(100);
+        assert_eq!(
+            mcts.proof(),
+            Some(Proof {
+                result: GameResult::Win(Player::First),
+                plies: 1
+            })
+        );
+        assert_eq!(mcts.best().unwrap().mv, 2);
+    }
+
+    #[test]
+    fn advance_keeps_subtree() {
+        let mut mcts =
+            Mcts::new(&Nim::with_extra_turns(30), RandomPlayout::new(3), 1.4).with_max_nodes(500);
+        mcts.run(2000);
+        assert!(mcts.node_count() <= 500 + 2);
+        let best = mcts.best().unwrap();
+        mcts.advance(best.mv);
+        assert_eq!(mcts.visits(), best.visits);
+        assert_eq!(mcts.root().pile, 30 - best.mv);
+        let moves = mcts.root_moves();
+        assert_eq!(moves.iter().map(|m| m.visits).sum::<u32>() + 1, best.visits);
+        mcts.run(100);
+        mcts.advance(1);
+        assert_eq!(mcts.root().pile, 29 - best.mv);
+    }
+}
@@ -0,0 +1,346 @@
+//! Negamax search with alpha-beta pruning over any [`Game`], deepened one ply at
+//! a time until a depth or time limit, with a transposition table and killer and
+//! history move ordering.
+//!
+//! Scores are for the player to move. A move that keeps the same player to move
+//! is searched without negating the score, so extra turns need no special care.
+use std::{cmp::Reverse, collections::HashMap, time::Duration};
+
+use crate::{time::Instant, Game};
+
+/// Score of a position won on the spot. A win `n` plies ahead scores
+/// `WIN_SCORE - n`, so faster wins and slower losses are preferred.
+pub const WIN_SCORE: i32 = 1_000_000;
+
+/// Scores beyond this are proven wins or losses. Evaluation functions must stay
+/// within it.
+pub const MATE_BOUND: i32 = WIN_SCORE - 10_000;
+
+const INFINITY: i32 = WIN_SCORE + 1;
+
+/// Plies to the end of the game for a proven win or loss score.
+pub fn mate_plies(score: i32) -> Option<u32> {
+    (score.abs() > MATE_BOUND).then(|| (WIN_SCORE - score.abs()) as u32)
+}
+
+/// Mate scores are stored relative to the node so that they stay valid when the
+/// same position is met at another ply.
+fn to_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: u32) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry<M> {
+    key: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best: Option<M>,
+}
+
+/// The result of the deepest finished iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchInfo<M> {
+    /// `None` when the game is over.
+    pub best: Option<M>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+/// Alpha-beta searcher with `evaluate` scoring unfinished positions at the depth
+/// limit for the player to move.
+pub struct Negamax<G: Game, E> {
+    evaluate: E,
+    max_depth: u32,
+    limit: Option<Duration>,
+    table: Vec<Option<Entry<G::Move>>>,
+    killers: Vec<[Option<G::Move>; 2]>,
+    history: HashMap<G::Move, u32>,
+    nodes: u64,
+    start: Instant,
+    can_abort: bool,
+    aborted: bool,
+    root_best: Option<G::Move>,
+}
+
+impl<G: Game, E: Fn(&G) -> i32> Negamax<G, E> {
+    pub fn new(evaluate: E, max_depth: u32) -> Negamax<G, E> {
+        Negamax {
+            evaluate,
+            max_depth,
+            limit: None,
+            table: vec![None; 1 << 16],
+            killers: Vec::new(),
+            history: HashMap::new(),
+            nodes: 0,
+            start: Instant::now(),
+            can_abort: false,
+            aborted: false,
+            root_best: None,
+        }
+    }
+
+    /// Stops deepening once `limit` has passed, keeping the last finished
+    /// iteration. The first iteration always finishes.
+    pub fn with_time_limit(mut self, limit: Duration) -> Negamax<G, E> {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Uses a transposition table of `2^bits` entries.
+    pub fn with_table_bits(mut self, bits: u32) -> Negamax<G, E> {
+        self.table = vec![None; 1 << bits];
+        self
+    }
+
+    /// Searches `game` deeper and deeper until the depth or time limit, or until
+    /// its result is proven.
+    pub fn search(&mut self, game: &G) -> SearchInfo<G::Move> {
+        self.table.iter_mut().for_each(|e| *e = None);
+        self.killers.clear();
+        self.history.clear();
+        self.nodes = 0;
+        self.start = Instant::now();
+        self.aborted = false;
+        let mut info = SearchInfo {
+            best: None,
+            score: self.negamax(game, 0, -INFINITY, INFINITY, 0),
+            depth: 0,
+            nodes: 0,
+        };
+        if game.result().is_none() {
+            for depth in 1..=self.max_depth {
+                self.can_abort = depth > 1;
+                self.root_best = None;
+                let score = self.negamax(game, depth, -INFINITY, INFINITY, 0);
+                if self.aborted {
+                    break;
+                }
+                info.best = self.root_best;
+                info.score = score;
+                info.depth = depth;
+                if mate_plies(score).is_some() {
+                    break;
+                }
+            }
+        }
+        info.nodes = self.nodes;
+        info
+    }
+
+    fn time_is_up(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.start.elapsed() >= limit)
+    }
+
+    fn probe(&self, key: u64) -> Option<Entry<G::Move>> {
+        self.table[key as usize & (self.table.len() - 1)].filter(|e| e.key == key)
+    }
+
+    fn store(&mut self, entry: Entry<G::Move>) {
+        let len = self.table.len();
+        self.table[entry.key as usize & (len - 1)] = Some(entry);
+    }
+
+    /// The move from the table first, then the killers of `ply`, then the rest
+    /// by history.
+    fn order(&self, moves: &mut [G::Move], table_move: Option<G::Move>, ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|&mv| {
+            let rank = if Some(mv) == table_move {
+                0
+            } else if Some(mv) == killers[0] {
+                1
+            } else if Some(mv) == killers[1] {
+                2
+            } else {
+                3
+            };
+            (rank, Reverse(self.history.get(&mv).copied().unwrap_or(0)))
+        });
+    }
+
+    fn remember_cutoff(&mut self, mv: G::Move, depth: u32, ply: usize) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+        *self.history.entry(mv).or_insert(0) += depth * depth;
+    }
+
+    fn negamax(&mut self, game: &G, depth: u32, mut alpha: i32, mut beta: i32, ply: u32) -> i32 {
+        self.nodes += 1;
+        if self.can_abort && self.nodes.is_multiple_of(1024) && self.time_is_up() {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+        let me = game.to_move();
+        if let Some(result) = game.result() {
+            return result.score(me) * (WIN_SCORE - ply as i32);
+        }
+        if depth == 0 {
+            return (self.evaluate)(game);
+        }
+
+        let key = game.hash_key();
+        let original_alpha = alpha;
+        let mut table_move = None;
+        if let Some(entry) = self.probe(key) {
+            table_move = entry.best;
+            if entry.depth >= depth && ply > 0 {
+                let score = from_table(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower => alpha = alpha.max(score),
+                    Bound::Upper => beta = beta.min(score),
+                }
+                if alpha >= beta {
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = game.legal_moves();
+        self.order(&mut moves, table_move, ply as usize);
+        let mut best = (-INFINITY, None);
+        for mv in moves {
+            let mut next = game.clone();
+            next.apply(mv);
+            let score = if next.to_move() == me {
+                self.negamax(&next, depth - 1, alpha, beta, ply + 1)
+            } else {
+                -self.negamax(&next, depth - 1, -beta, -alpha, ply + 1)
+            };
+            if self.aborted {
+                return 0;
+            }
+            if score > best.0 {
+                best = (score, Some(mv));
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                self.remember_cutoff(mv, depth, ply as usize);
+                break;
+            }
+        }
+
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        } else if best.0 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.store(Entry {
+            key,
+            depth,
+            score: to_table(best.0, ply),
+            bound,
+            best: best.1,
+        });
+        if ply == 0 {
+            self.root_best = best.1;
+        }
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    /// Exact score by plain minimax.
+    fn solve(game: &Nim, ply: i32) -> i32 {
+        if let Some(result) = game.result() {
+            return result.score(game.to_move()) * (WIN_SCORE - ply);
+        }
+        game.legal_moves()
+            .into_iter()
+            .map(|mv| {
+                let mut next = game.clone();
+                next.apply(mv);
+                let score = solve(&next, ply + 1);
+                if next.to_move() == game.to_move() {
+                    score
+                } else {
+                    -score
+                }
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn matches_minimax() {
+        let mut negamax = Negamax::new(|_: &Nim| 0, 30);
+        for pile in 1..=14 {
+            let game = Nim::with_extra_turns(pile);
+            let info = negamax.search(&game);
+            assert_eq!(info.score, solve(&game, 0), "pile {}", pile);
+            let mut next = game.clone();
+            next.apply(info.best.unwrap());
+            let after = solve(&next, 1);
+            let after = if next.to_move() == game.to_move() {
+                after
+            } else {
+                -after
+            };
+            assert_eq!(after, info.score, "pile {}", pile);
+        }
+    }
+
+    #[test]
+    fn finished_and_depth_limited() {
+        let info = Negamax::new(|_: &Nim| 0, 5).search(&Nim::with_extra_turns(0));
+        assert_eq!(info.best, None);
+        assert_eq!(info.score, -WIN_SCORE);
+        assert_eq!(mate_plies(info.score), Some(0));
+
+        // The evaluation decides when no result is in reach.
+        let info = Negamax::new(|g: &Nim| g.pile as i32, 1).search(&Nim::with_extra_turns(100));
+        assert_eq!(info.depth, 1);
+        assert_eq!(mate_plies(info.score), None);
+        assert_eq!(info.best, Some(3));
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut negamax = Negamax::new(|_: &Nim| 0, 1000)
+            .with_time_limit(Duration::from_millis(20))
+            .with_table_bits(4);
+        let info = negamax.search(&Nim::with_extra_turns(5000));
+        assert!(info.best.is_some());
+        assert!(info.depth < 1000);
+    }
+}
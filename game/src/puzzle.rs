@@ -0,0 +1,218 @@
+//! "Win in N" puzzles: positions where the player to move has exactly one move
+//! that forces a win within a number of plies, mined from random self-play.
+use std::collections::{HashMap, HashSet};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{Game, GameResult, Player};
+
+/// Plies after which a self-play game is abandoned, for games that can cycle.
+const MAX_GAME_PLIES: usize = 400;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Puzzle<M> {
+    /// Moves from the start to the puzzle position.
+    pub moves: Vec<M>,
+    pub to_move: Player,
+    /// Plies to the win, counting the moves of both players.
+    pub plies: u32,
+    /// The winning line: the only winning move, the longest defence, the
+    /// fastest win and so on to the end. Consecutive moves of one player are extra turns.
+    pub solution: Vec<M>,
+}
+
+/// Puzzles of one game under the rules of `variant`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PuzzleSet<V, M> {
+    pub game: String,
+    pub variant: V,
+    pub puzzles: Vec<Puzzle<M>>,
+}
+
+/// Exact search for forced wins of one player.
+struct Solver {
+    player: Player,
+    memo: HashMap<(u64, u32), bool>,
+}
+
+impl Solver {
+    fn new(player: Player) -> Solver {
+        Solver {
+            player,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Whether the player wins within `plies` against any defence.
+    fn wins_within<G: Game>(&mut self, game: &G, plies: u32) -> bool {
+        if let Some(result) = game.result() {
+            return result == GameResult::Win(self.player);
+        }
+        if plies == 0 {
+            return false;
+        }
+        let key = (game.hash_key(), plies);
+        if let Some(&wins) = self.memo.get(&key) {
+            return wins;
+        }
+        let attacking = game.to_move() == self.player;
+        let mut wins = !attacking;
+        for mv in game.legal_moves() {
+            let mut next = game.clone();
+            next.apply(mv);
+            if self.wins_within(&next, plies - 1) == attacking {
+                wins = attacking;
+                break;
+            }
+        }
+        self.memo.insert(key, wins);
+        wins
+    }
+
+    /// Fewest plies to a forced win, if at most `max_plies`.
+    fn distance<G: Game>(&mut self, game: &G, max_plies: u32) -> Option<u32> {
+        (0..=max_plies).find(|&plies| self.wins_within(game, plies))
+    }
+
+    /// Fastest moves of the player and slowest of the opponent from `game`,
+    /// which is won in `plies`.
+    fn line<G: Game>(&mut self, game: &G, mut plies: u32) -> Vec<G::Move> {
+        let mut game = game.clone();
+        let mut line = Vec::new();
+        while game.result().is_none() {
+            let attacking = game.to_move() == self.player;
+            let mut choice = None;
+            for mv in game.legal_moves() {
+                let mut next = game.clone();
+                next.apply(mv);
+                let d = self.distance(&next, plies - 1).unwrap_or(u32::MAX);
+                let better = match choice {
+                    None => d != u32::MAX,
+                    Some((_, best)) => (attacking && d < best) || (!attacking && d > best),
+                };
+                if better && d != u32::MAX {
+                    choice = Some((mv, d));
+                }
+            }
+            let (mv, d) = choice.expect("a won position has a winning line");
+            game.apply(mv);
+            line.push(mv);
+            plies = d;
+        }
+        line
+    }
+}
+
+/// Whether `player` forces a win from `game` within `plies`.
+pub fn wins_within<G: Game>(game: &G, player: Player, plies: u32) -> bool {
+    Solver::new(player).wins_within(game, plies)
+}
+
+/// Fewest plies in which the player to move forces a win, if at most
+/// `max_plies`.
+pub fn forced_win<G: Game>(game: &G, max_plies: u32) -> Option<u32> {
+    Solver::new(game.to_move()).distance(game, max_plies)
+}
+
+/// The puzzle of `game` if its player to move has a single move that forces a
+/// win within `max_plies`, and the fastest win takes at least `min_plies`.
+/// `moves` are those that led to `game`.
+pub fn find_puzzle<G: Game>(
+    game: &G,
+    moves: &[G::Move],
+    min_plies: u32,
+    max_plies: u32,
+) -> Option<Puzzle<G::Move>> {
+    let mut solver = Solver::new(game.to_move());
+    let plies = solver.distance(game, max_plies)?;
+    if plies < min_plies.max(1) {
+        return None;
+    }
+    let mut winning = 0;
+    for mv in game.legal_moves() {
+        let mut next = game.clone();
+        next.apply(mv);
+        if solver.wins_within(&next, max_plies - 1) {
+            winning += 1;
+        }
+    }
+    (winning == 1).then(|| Puzzle {
+        moves: moves.to_vec(),
+        to_move: game.to_move(),
+        plies,
+        solution: solver.line(game, plies),
+    })
+}
+
+/// Puzzles from `games` games of uniformly random moves from `start`, each
+/// position at most once.
+pub fn mine<G: Game>(
+    start: &G,
+    games: u32,
+    min_plies: u32,
+    max_plies: u32,
+    seed: u64,
+) -> Vec<Puzzle<G::Move>> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut seen = HashSet::new();
+    let mut puzzles = Vec::new();
+    for _ in 0..games {
+        let mut game = start.clone();
+        let mut moves = Vec::new();
+        while game.result().is_none() && moves.len() < MAX_GAME_PLIES {
+            if seen.insert(game.hash_key()) {
+                puzzles.extend(find_puzzle(&game, &moves, min_plies, max_plies));
+            }
+            let legal = game.legal_moves();
+            let mv = legal[rng.gen_range(0..legal.len())];
+            game.apply(mv);
+            moves.push(mv);
+        }
+    }
+    puzzles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    #[test]
+    fn forced_wins() {
+        assert_eq!(forced_win(&Nim::with_extra_turns(2), 5), Some(1));
+        // Taking all 3 wins at once.
+        assert_eq!(forced_win(&Nim::with_extra_turns(3), 5), Some(1));
+        // Taking 3 from 4 keeps the turn and needs one more move.
+        assert_eq!(forced_win(&Nim::with_extra_turns(4), 5), Some(2));
+        assert!(wins_within(&Nim::with_extra_turns(4), Player::First, 2));
+        assert!(!wins_within(&Nim::with_extra_turns(4), Player::First, 1));
+        assert!(!wins_within(&Nim::with_extra_turns(4), Player::Second, 5));
+        assert_eq!(forced_win(&Nim::with_extra_turns(6), 1), None);
+        assert!(forced_win(&Nim::with_extra_turns(6), 5).is_some());
+    }
+
+    #[test]
+    fn puzzles() {
+        // Only taking 3 and then the last one wins in two plies.
+        let puzzle = find_puzzle(&Nim::with_extra_turns(4), &[], 1, 5).unwrap();
+        assert_eq!(puzzle.plies, 2);
+        assert_eq!(puzzle.solution, vec![3, 1]);
+        assert_eq!(puzzle.to_move, Player::First);
+        assert_eq!(find_puzzle(&Nim::with_extra_turns(4), &[], 1, 1), None);
+        assert_eq!(find_puzzle(&Nim::with_extra_turns(4), &[], 3, 5), None);
+
+        let puzzles = mine(&Nim::with_extra_turns(9), 20, 2, 4, 1);
+        assert!(!puzzles.is_empty());
+        for puzzle in puzzles {
+            let mut game = Nim::with_extra_turns(9);
+            for &mv in puzzle.moves.iter().chain(&puzzle.solution) {
+                assert!(game.legal_moves().contains(&mv));
+                game.apply(mv);
+            }
+            assert_eq!(game.result(), Some(GameResult::Win(puzzle.to_move)));
+            assert!((2..=4).contains(&puzzle.plies));
+        }
+    }
+}
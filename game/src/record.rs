@@ -0,0 +1,210 @@
+//! A JSON format for played games, shared by every game so that games can be
+//! saved, shared and replayed.
+//!
+//! ```json
+//! {
+//!   "game": "connect-four",
+//!   "variant": {},
+//!   "players": [{"name": "alice"}, {"ai": "mctree:1000:2:2.0"}],
+//!   "seed": 42,
+//!   "moves": [{"move": 3, "eval": 0.55, "at": 1700000000000}, {"move": 3}],
+//!   "result": "first",
+//!   "startedAt": 1700000000000
+//! }
+//! ```
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Game, GameResult, Player};
+
+/// Who played one side.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The AI spec, for a side played by an AI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveRecord<M> {
+    #[serde(rename = "move")]
+    pub mv: M,
+    /// Evaluation of the position after the move for the player who made it, on
+    /// the scale of whoever evaluated it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<f64>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub at: Option<u64>,
+}
+
+/// [`GameResult`] as recorded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    First,
+    Second,
+    Draw,
+}
+
+impl From<GameResult> for Outcome {
+    fn from(result: GameResult) -> Outcome {
+        match result {
+            GameResult::Win(Player::First) => Outcome::First,
+            GameResult::Win(Player::Second) => Outcome::Second,
+            GameResult::Draw => Outcome::Draw,
+        }
+    }
+}
+
+/// A game from its start under the rules of `variant`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameRecord<V, M> {
+    /// `"tic-tac-toe"`, `"connect-four"`, `"kalah"` or `"oware"`.
+    pub game: String,
+    pub variant: V,
+    #[serde(default)]
+    pub players: [PlayerInfo; 2],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    pub moves: Vec<MoveRecord<M>>,
+    /// `None` for an unfinished game.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Outcome>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+}
+
+impl<V, M> GameRecord<V, M>
+where
+    V: Serialize + DeserializeOwned,
+    M: Copy + Eq + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    /// An empty record of `game`.
+    pub fn new(game: &str, variant: V) -> GameRecord<V, M> {
+        GameRecord {
+            game: game.to_owned(),
+            variant,
+            players: Default::default(),
+            seed: None,
+            moves: Vec::new(),
+            result: None,
+            started_at: None,
+            finished_at: None,
+        }
+    }
+
+    /// Records `mv` and the result once the game is over.
+    pub fn push<G: Game<Move = M>>(&mut self, game: &G, mv: M, eval: Option<f64>) {
+        self.moves.push(MoveRecord { mv, eval, at: None });
+        self.result = game.result().map(Outcome::from);
+    }
+
+    /// Moves without their annotations.
+    pub fn move_list(&self) -> Vec<M> {
+        self.moves.iter().map(|m| m.mv).collect()
+    }
+
+    /// Plays the moves from `start`, checking that the record is of `game`, that
+    /// every move is legal and that the result is the one reached.
+    pub fn replay<G: Game<Move = M>>(&self, game: &str, mut start: G) -> Result<G, String> {
+        if self.game != game {
+            return Err(format!("a record of {}, not {}", self.game, game));
+        }
+        for (ply, m) in self.moves.iter().enumerate() {
+            if !start.legal_moves().contains(&m.mv) {
+                return Err(format!("illegal move {:?} at ply {}", m.mv, ply));
+            }
+            start.apply(m.mv);
+        }
+        let reached = start.result().map(Outcome::from);
+        if self.result != reached {
+            return Err(format!(
+                "recorded result {:?} but the moves lead to {:?}",
+                self.result, reached
+            ));
+        }
+        Ok(start)
+    }
+
+    /// Reads a record of `game`, checked by replaying it from the start that
+    /// `start` builds for its variant.
+    pub fn parse<G: Game<Move = M>>(
+        json: &str,
+        game: &str,
+        start: impl FnOnce(&V) -> Result<G, String>,
+    ) -> Result<GameRecord<V, M>, String> {
+        let record: GameRecord<V, M> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        record.replay(game, start(&record.variant)?)?;
+        Ok(record)
+    }
+
+    /// Writes the record as pretty JSON after the checks of [`Self::parse`].
+    pub fn emit<G: Game<Move = M>>(
+        &self,
+        game: &str,
+        start: impl FnOnce(&V) -> Result<G, String>,
+    ) -> Result<String, String> {
+        self.replay(game, start(&self.variant)?)?;
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Nim;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Pile {
+        pile: u32,
+    }
+
+    fn start(variant: &Pile) -> Result<Nim, String> {
+        Ok(Nim::new(variant.pile))
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut record = GameRecord::new("nim", Pile { pile: 4 });
+        record.players[1].ai = Some("perfect".to_owned());
+        record.seed = Some(3);
+        let mut nim = start(&record.variant).unwrap();
+        for mv in [1, 2, 1] {
+            nim.apply(mv);
+            record.push(&nim, mv, Some(0.5));
+        }
+        assert_eq!(record.result, Some(Outcome::First));
+        assert_eq!(record.move_list(), vec![1, 2, 1]);
+
+        let json = record.emit("nim", start).unwrap();
+        assert!(json.contains(r#""move": 2"#));
+        assert!(!json.contains("startedAt"));
+        let parsed = GameRecord::parse(&json, "nim", start).unwrap();
+        assert_eq!(parsed, record);
+    }
+
+    #[test]
+    fn checks() {
+        let json = r#"{"game": "nim", "variant": {"pile": 3}, "moves": [{"move": 2}]}"#;
+        let record = GameRecord::<Pile, u32>::parse(json, "nim", start).unwrap();
+        assert_eq!(
+            record.players,
+            [PlayerInfo::default(), PlayerInfo::default()]
+        );
+        assert_eq!(record.result, None);
+        assert!(GameRecord::<Pile, u32>::parse(json, "chess", start).is_err());
+
+        let illegal = json.replace(r#""move": 2"#, r#""move": 3"#);
+        assert!(GameRecord::<Pile, u32>::parse(&illegal, "nim", start).is_err());
+        let wrong = json.replace("]}", r#"], "result": "draw"}"#);
+        assert!(GameRecord::<Pile, u32>::parse(&wrong, "nim", start).is_err());
+        assert!(GameRecord::<Pile, u32>::parse("{}", "nim", start).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Small games for the tests of the searchers and tools.
+use crate::{hash_of, Game, GameResult, Player};
+
+/// Take 1 or 2 from a pile; who takes the last one wins. With extra turns, taking
+/// 3 is allowed too and keeps the turn unless it empties the pile.
+#[derive(Debug, Clone, Hash)]
+pub(crate) struct Nim {
+    pub(crate) pile: u32,
+    pub(crate) to_move: Player,
+    pub(crate) extra_turns: bool,
+}
+
+impl Nim {
+    pub(crate) fn new(pile: u32) -> Nim {
+        Nim {
+            pile,
+            to_move: Player::First,
+            extra_turns: false,
+        }
+    }
+
+    pub(crate) fn with_extra_turns(pile: u32) -> Nim {
+        Nim {
+            extra_turns: true,
+            ..Nim::new(pile)
+        }
+    }
+}
+
+impl Game for Nim {
+    type Move = u32;
+
+    fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    fn legal_moves(&self) -> Vec<u32> {
+        let most = if self.extra_turns { 3 } else { 2 };
+        (1..=self.pile.min(most)).collect()
+    }
+
+    fn apply(&mut self, mv: u32) {
+        self.pile -= mv;
+        if mv != 3 || self.pile == 0 {
+            self.to_move = self.to_move.flip();
+        }
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        (self.pile == 0).then_some(GameResult::Win(self.to_move.flip()))
+    }
+
+    fn hash_key(&self) -> u64 {
+        hash_of(self)
+    }
+}
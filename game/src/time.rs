@@ -0,0 +1,24 @@
+//! A clock for the time limits of the searchers. `std::time::Instant` panics on
+//! `wasm32-unknown-unknown`, so there it reads `Date.now()` of JavaScript.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use std::time::Duration;
+
+/// Milliseconds since the epoch, with the methods of `std::time::Instant` the
+/// searchers use.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct Instant(f64);
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    pub fn now() -> Instant {
+        Instant(js_sys::Date::now())
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs_f64((js_sys::Date::now() - self.0).max(0.0) / 1000.0)
+    }
+}
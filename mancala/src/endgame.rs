@@ -0,0 +1,303 @@
+//! Endgame table for Kalah positions with few seeds left on the board.
+//!
+//! Only the seeds still on the board matter for the rest of the game, so a
+//! position is keyed by the row of the side to move followed by the row of
+//! the opponent, and its value is how many more of those seeds the side to
+//! move ends up with than the opponent under perfect play.
+//!
+//! Every sowing either drops a seed in the own store or moves own seeds closer
+//! to it, so positions only lead to positions that are strictly smaller in
+//! (seeds on board, distance to stores) order. Values are therefore filled
+//! from the smallest positions up without ever meeting a cycle.
+use std::sync::{Arc, Mutex};
+
+use crate::{Side, kalah::Board};
+
+/// Seeds on the board up to which [`shared`] tables are built.
+pub const ENDGAME_SEEDS: u8 = 8;
+
+const UNKNOWN: i8 = i8::MIN;
+
+pub struct EndgameTable {
+    pits: usize,
+    stealing: bool,
+    max_seeds: u8,
+    /// `binom[l][n]` is the number of rows of length `l` holding at most `n` seeds.
+    binom: Vec<Vec<usize>>,
+    values: Vec<i8>,
+}
+
+impl EndgameTable {
+    /// Builds the table of every position with at most `max_seeds` seeds on the board.
+    pub fn new(pits: usize, stealing: bool, max_seeds: u8) -> Result<EndgameTable, String> {
+        Board::new(pits, *crate::kalah::SEEDS.start(), stealing)?;
+        if max_seeds as usize > i8::MAX as usize {
+            return Err(format!("max_seeds is too large: {}", max_seeds));
+        }
+        let len = pits * 2;
+        let n = max_seeds as usize;
+        let mut binom = vec![vec![1; n + 1]; len + 1];
+        for l in 1..=len {
+            for s in 1..=n {
+                binom[l][s] = binom[l][s - 1] + binom[l - 1][s];
+            }
+        }
+        let mut table = EndgameTable {
+            pits,
+            stealing,
+            max_seeds,
+            values: vec![UNKNOWN; binom[len][n]],
+            binom,
+        };
+        let mut row = vec![0; len];
+        table.fill(&mut row, 0, max_seeds);
+        Ok(table)
+    }
+
+    pub fn pits(&self) -> usize {
+        self.pits
+    }
+
+    pub fn stealing(&self) -> bool {
+        self.stealing
+    }
+
+    pub fn max_seeds(&self) -> u8 {
+        self.max_seeds
+    }
+
+    fn fill(&mut self, row: &mut [u8], i: usize, rest: u8) {
+        if i == row.len() {
+            let (own, opp) = row.split_at(self.pits);
+            self.solve(own, opp);
+            return;
+        }
+        for s in 0..=rest {
+            row[i] = s;
+            self.fill(row, i + 1, rest - s);
+        }
+        row[i] = 0;
+    }
+
+    fn index(&self, own: &[u8], opp: &[u8]) -> usize {
+        let len = self.pits * 2;
+        let mut rest = self.max_seeds as usize;
+        let mut index = 0;
+        for (i, &s) in own.iter().chain(opp.iter()).enumerate() {
+            for x in 0..s as usize {
+                index += self.binom[len - i - 1][rest - x];
+            }
+            rest -= s as usize;
+        }
+        index
+    }
+
+    fn solve(&mut self, own: &[u8], opp: &[u8]) -> i8 {
+        let index = self.index(own, opp);
+        if self.values[index] != UNKNOWN {
+            return self.values[index];
+        }
+        let board = Board::from_parts(
+            Side::First,
+            self.stealing,
+            [own.to_vec(), opp.to_vec()],
+            [0, 0],
+        )
+        .expect("invalid endgame position");
+        let value = if board.is_finished() {
+            let (a, b) = board.last_scores();
+            a as i8 - b as i8
+        } else {
+            let mut best = i8::MIN + 1;
+            for pos in board.list_can_sow() {
+                let mut next = board.clone();
+                next.sow(pos);
+                let [a, b] = next.score();
+                let [own, opp] = next.seeds();
+                let rest = match next.side() {
+                    Side::First => self.solve(own, opp),
+                    Side::Second => -self.solve(opp, own),
+                };
+                best = best.max(a as i8 - b as i8 + rest);
+            }
+            best
+        };
+        self.values[index] = value;
+        value
+    }
+
+    fn covers(&self, board: &Board) -> bool {
+        board.pits() == self.pits
+            && board.stealing() == self.stealing
+            && board
+                .seeds()
+                .iter()
+                .flatten()
+                .map(|&s| s as usize)
+                .sum::<usize>()
+                <= self.max_seeds as usize
+    }
+
+    /// How many more of the seeds left on the board the side to move collects than
+    /// the opponent, or `None` when the position is not in the table.
+    pub fn value(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        let [first, second] = board.seeds();
+        let index = match board.side() {
+            Side::First => self.index(first, second),
+            Side::Second => self.index(second, first),
+        };
+        Some(self.values[index] as i32)
+    }
+
+    /// A sowing with the best exact value, or `None` when the position is not in the
+    /// table or the game is over.
+    pub fn best_move(&self, board: &Board) -> Option<usize> {
+        let me = board.side().index();
+        board
+            .list_can_sow()
+            .into_iter()
+            .map(|pos| {
+                let mut next = board.clone();
+                next.sow(pos);
+                let scores = self.final_scores(&next)?;
+                let scores = [scores.0 as i32, scores.1 as i32];
+                Some((scores[me] - scores[1 - me], pos))
+            })
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max_by_key(|&(value, _)| value)
+            .map(|(_, pos)| pos)
+    }
+
+    /// Exact final scores under perfect play, or `None` when the position is not in
+    /// the table.
+    pub fn final_scores(&self, board: &Board) -> Option<(u8, u8)> {
+        let value = self.value(board)?;
+        let rest = board.seeds().iter().flatten().sum::<u8>() as i32;
+        let mover = ((rest + value) / 2) as u8;
+        let other = rest as u8 - mover;
+        let [a, b] = board.score();
+        Some(match board.side() {
+            Side::First => (a + mover, b + other),
+            Side::Second => (a + other, b + mover),
+        })
+    }
+}
+
+static TABLES: Mutex<Vec<Arc<EndgameTable>>> = Mutex::new(Vec::new());
+
+/// The table for boards shaped like `board`, built on first use and kept for the
+/// life of the process.
+pub fn shared(board: &Board) -> Arc<EndgameTable> {
+    shared_shape(board.pits(), board.stealing()).expect("invalid board shape")
+}
+
+/// [`shared`] by the shape of the board, for building a table before the first
+/// search needs it.
+pub fn shared_shape(pits: usize, stealing: bool) -> Result<Arc<EndgameTable>, String> {
+    let mut tables = TABLES.lock().unwrap();
+    if let Some(table) = tables
+        .iter()
+        .find(|t| t.pits == pits && t.stealing == stealing)
+    {
+        return Ok(table.clone());
+    }
+    let table = Arc::new(EndgameTable::new(pits, stealing, ENDGAME_SEEDS)?);
+    tables.push(table.clone());
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plain minimax over whole games, for checking the table.
+    fn minimax(board: &Board) -> i32 {
+        if board.is_finished() {
+            let (a, b) = board.last_scores();
+            return match board.side() {
+                Side::First => a as i32 - b as i32,
+                Side::Second => b as i32 - a as i32,
+            };
+        }
+        board
+            .list_can_sow()
+            .into_iter()
+            .map(|pos| {
+                let mut next = board.clone();
+                next.sow(pos);
+                if next.side() == board.side() {
+                    minimax(&next)
+                } else {
+                    -minimax(&next)
+                }
+            })
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn size() {
+        // Rows of 8 pits with up to 3 seeds: C(3 + 8, 8).
+        let table = EndgameTable::new(4, true, 3).unwrap();
+        assert_eq!(table.values.len(), 165);
+        assert!(table.values.iter().all(|&v| v != UNKNOWN));
+        assert!(EndgameTable::new(3, true, 3).is_err());
+    }
+
+    #[test]
+    fn matches_minimax() {
+        for stealing in [true, false] {
+            let table = EndgameTable::new(4, stealing, 6).unwrap();
+            let positions = [
+                (Side::First, [vec![1, 0, 2, 0], vec![0, 1, 0, 2]], [10, 8]),
+                (Side::Second, [vec![0, 3, 0, 0], vec![1, 1, 0, 1]], [12, 6]),
+                (Side::First, [vec![0, 0, 0, 1], vec![2, 0, 0, 0]], [11, 11]),
+                (Side::Second, [vec![1, 1, 1, 1], vec![1, 0, 0, 1]], [9, 9]),
+            ];
+            for (side, seeds, score) in positions {
+                let board = Board::from_parts(side, stealing, seeds, score).unwrap();
+                let (a, b) = table.final_scores(&board).unwrap();
+                assert_eq!(a + b, board.total_seeds());
+                let diff = match side {
+                    Side::First => a as i32 - b as i32,
+                    Side::Second => b as i32 - a as i32,
+                };
+                let [sa, sb] = board.score();
+                let stores = match side {
+                    Side::First => sa as i32 - sb as i32,
+                    Side::Second => sb as i32 - sa as i32,
+                };
+                assert_eq!(diff, minimax(&board));
+                assert_eq!(table.value(&board), Some(diff - stores));
+            }
+        }
+    }
+
+    #[test]
+    fn best_move() {
+        let table = EndgameTable::new(4, false, 6).unwrap();
+        // Sowing 3, then 2, then 3 takes every seed on the own row.
+        let board = Board::from_parts(
+            Side::First,
+            false,
+            [vec![0, 0, 2, 1], vec![3, 0, 0, 0]],
+            [4, 4],
+        )
+        .unwrap();
+        assert_eq!(table.best_move(&board), Some(3));
+        assert_eq!(table.final_scores(&board), Some((7, 7)));
+    }
+
+    #[test]
+    fn out_of_table() {
+        let table = EndgameTable::new(4, true, 4).unwrap();
+        let board = Board::new(4, 3, true).unwrap();
+        assert_eq!(table.value(&board), None);
+        let board = Board::from_parts(Side::First, false, [vec![1; 4], vec![0; 4]], [0, 0]);
+        assert_eq!(table.value(&board.unwrap()), None);
+    }
+}
@@ -0,0 +1,602 @@
+//! Kalah(n, m): `n` pits per side, `m` seeds per pit.
+//!
+//! Seeds are sown counter-clockwise into the own store but never into the
+//! opponent's. A turn ending in the own store gives an extra turn. With
+//! `stealing`, a turn ending in an own empty pit captures that seed together
+//! with the seeds of the opposite pit. The game ends when either row is empty
+//! and the remaining seeds go to the owner of the row.
+use std::{fmt::Display, ops::RangeInclusive, str::FromStr};
+
+use game::{Game, GameResult, Player, hash_of};
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::IndexedRandom, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Side,
+    endgame::{self, EndgameTable},
+    mctree::McTreeAI,
+    side::final_result,
+};
+
+pub const PITS: RangeInclusive<usize> = 4..=8;
+pub const SEEDS: RangeInclusive<u8> = 3..=6;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Board {
+    side: Side,
+    stealing: bool,
+    seeds: [Vec<u8>; 2],
+    score: [u8; 2],
+}
+
+impl Board {
+    pub fn new(pits: usize, seeds: u8, stealing: bool) -> Result<Board, String> {
+        if !PITS.contains(&pits) {
+            return Err(format!(
+                "pits must be in {}..={}: {}",
+                PITS.start(),
+                PITS.end(),
+                pits
+            ));
+        }
+        if !SEEDS.contains(&seeds) {
+            return Err(format!(
+                "seeds must be in {}..={}: {}",
+                SEEDS.start(),
+                SEEDS.end(),
+                seeds
+            ));
+        }
+        Ok(Board {
+            side: Side::First,
+            stealing,
+            seeds: [vec![seeds; pits], vec![seeds; pits]],
+            score: [0, 0],
+        })
+    }
+
+    pub fn from_parts(
+        side: Side,
+        stealing: bool,
+        seeds: [Vec<u8>; 2],
+        score: [u8; 2],
+    ) -> Result<Board, String> {
+        let board = Board {
+            side,
+            stealing,
+            seeds,
+            score,
+        };
+        board.validate()?;
+        Ok(board)
+    }
+
+    /// Checks the shape of a board that came from outside, e.g. through serde.
+    pub fn validate(&self) -> Result<(), String> {
+        let pits = self.seeds[0].len();
+        if !PITS.contains(&pits) || self.seeds[1].len() != pits {
+            return Err(format!(
+                "both rows must have the same number of pits in {}..={}",
+                PITS.start(),
+                PITS.end()
+            ));
+        }
+        let total = self
+            .seeds
+            .iter()
+            .flatten()
+            .map(|&s| s as usize)
+            .sum::<usize>()
+            + self.score.iter().map(|&s| s as usize).sum::<usize>();
+        let max = PITS.end() * *SEEDS.end() as usize * 2;
+        if total > max {
+            return Err(format!("too many seeds: {} > {}", total, max));
+        }
+        Ok(())
+    }
+
+    /// Whether this is the 6-pit, 4-seed shape played by `mancala_rust`.
+    pub fn is_standard(&self) -> bool {
+        self.pits() == 6 && self.total_seeds() == 48
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn stealing(&self) -> bool {
+        self.stealing
+    }
+
+    pub fn pits(&self) -> usize {
+        self.seeds[0].len()
+    }
+
+    pub fn seeds(&self) -> &[Vec<u8>; 2] {
+        &self.seeds
+    }
+
+    pub fn score(&self) -> [u8; 2] {
+        self.score
+    }
+
+    pub fn total_seeds(&self) -> u8 {
+        self.seeds.iter().flatten().sum::<u8>() + self.score[0] + self.score[1]
+    }
+
+    fn row_sum(&self, side: Side) -> u8 {
+        self.seeds[side.index()].iter().sum()
+    }
+
+    pub fn can_sow(&self, pos: usize) -> Result<(), String> {
+        if pos >= self.pits() {
+            return Err(format!("pit {} is out of range", pos));
+        }
+        if self.seeds[self.side.index()][pos] == 0 {
+            return Err(format!("pit {} is empty", pos));
+        }
+        Ok(())
+    }
+
+    pub fn list_can_sow(&self) -> Vec<usize> {
+        (0..self.pits())
+            .filter(|&pos| self.seeds[self.side.index()][pos] > 0)
+            .collect()
+    }
+
+    /// Plays `pos` for the side to move. The move must satisfy [`Board::can_sow`].
+    ///
+    /// The side to move is left unchanged when the move earns an extra turn.
+    pub fn sow(&mut self, pos: usize) {
+        debug_assert!(self.can_sow(pos).is_ok());
+        let pits = self.pits();
+        let me = self.side;
+        let mut n = self.seeds[me.index()][pos];
+        self.seeds[me.index()][pos] = 0;
+        // Positions 0..pits are own pits, `pits` is the own store and
+        // pits+1..=2*pits are the opponent's pits.
+        let cycle = pits * 2 + 1;
+        let mut cur = pos;
+        while n > 0 {
+            cur = (cur + 1) % cycle;
+            match cur {
+                c if c < pits => self.seeds[me.index()][c] += 1,
+                c if c == pits => self.score[me.index()] += 1,
+                c => self.seeds[me.flip().index()][c - pits - 1] += 1,
+            }
+            n -= 1;
+        }
+        if cur == pits {
+            if !self.is_finished() {
+                return;
+            }
+        } else if cur < pits && self.stealing && self.seeds[me.index()][cur] == 1 {
+            let opposite = pits - 1 - cur;
+            let stolen = self.seeds[me.flip().index()][opposite];
+            if stolen > 0 {
+                self.seeds[me.flip().index()][opposite] = 0;
+                self.seeds[me.index()][cur] = 0;
+                self.score[me.index()] += stolen + 1;
+            }
+        }
+        self.side = me.flip();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.row_sum(Side::First) == 0 || self.row_sum(Side::Second) == 0
+    }
+
+    /// Final scores, with the seeds left on the board going to the row's owner.
+    pub fn last_scores(&self) -> (u8, u8) {
+        (
+            self.score[0] + self.row_sum(Side::First),
+            self.score[1] + self.row_sum(Side::Second),
+        )
+    }
+}
+
+impl Game for Board {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        self.side.into()
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.is_finished() {
+            Vec::new()
+        } else {
+            self.list_can_sow()
+        }
+    }
+
+    fn apply(&mut self, pos: usize) {
+        self.sow(pos);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        if !self.is_finished() {
+            return None;
+        }
+        Some(final_result(self.last_scores()))
+    }
+
+    fn hash_key(&self) -> u64 {
+        hash_of(self)
+    }
+}
+
+pub trait AI {
+    /// Returns the pits of one full turn, including extra turns.
+    fn sow(&mut self, board: &Board) -> Vec<usize>;
+}
+
+pub struct RandomAI<R> {
+    rng: R,
+}
+
+impl<R: Rng> RandomAI<R> {
+    pub fn new(rng: R) -> RandomAI<R> {
+        RandomAI { rng }
+    }
+}
+
+impl<R: Rng> AI for RandomAI<R> {
+    fn sow(&mut self, board: &Board) -> Vec<usize> {
+        let mut board = board.clone();
+        let side = board.side;
+        let mut turn = Vec::new();
+        while board.side == side && !board.is_finished() {
+            let pos = *board.list_can_sow().choose(&mut self.rng).expect("no move");
+            board.sow(pos);
+            turn.push(pos);
+        }
+        turn
+    }
+}
+
+/// Negamax with alpha-beta pruning on the store difference.
+///
+/// One ply is one sowing, so an extra turn costs depth without flipping sides.
+/// Positions covered by the [endgame table](crate::endgame) are scored exactly.
+pub struct DfsAI<R> {
+    rng: R,
+    depth: u32,
+}
+
+impl<R: Rng> DfsAI<R> {
+    pub fn new(rng: R, depth: u32) -> DfsAI<R> {
+        DfsAI { rng, depth }
+    }
+
+    fn evaluate(board: &Board) -> i32 {
+        let me = board.side.index();
+        let s = if board.is_finished() {
+            let (a, b) = board.last_scores();
+            [a as i32, b as i32]
+        } else {
+            [board.score[0] as i32, board.score[1] as i32]
+        };
+        s[me] - s[1 - me]
+    }
+
+    fn negamax(table: &EndgameTable, board: &Board, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+        if let Some(value) = table.value(board) {
+            let me = board.side.index();
+            return board.score[me] as i32 - board.score[1 - me] as i32 + value;
+        }
+        if depth == 0 || board.is_finished() {
+            return Self::evaluate(board);
+        }
+        let mut best = i32::MIN + 1;
+        for pos in board.list_can_sow() {
+            let mut next = board.clone();
+            next.sow(pos);
+            let v = if next.side == board.side {
+                Self::negamax(table, &next, depth - 1, alpha, beta)
+            } else {
+                -Self::negamax(table, &next, depth - 1, -beta, -alpha)
+            };
+            best = best.max(v);
+            alpha = alpha.max(v);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    fn choose(&mut self, table: &EndgameTable, board: &Board) -> usize {
+        let mut moves = board.list_can_sow();
+        moves.shuffle(&mut self.rng);
+        let mut best = (i32::MIN + 1, moves[0]);
+        for pos in moves {
+            let mut next = board.clone();
+            next.sow(pos);
+            let v = if next.side == board.side {
+                Self::negamax(table, &next, self.depth - 1, best.0, i32::MAX)
+            } else {
+                -Self::negamax(table, &next, self.depth - 1, i32::MIN + 1, -best.0)
+            };
+            if v > best.0 {
+                best = (v, pos);
+            }
+        }
+        best.1
+    }
+
+    /// Evaluates every legal pit for the side to move, and the full turn this
+    /// player would take.
+    pub fn hint(&mut self, board: &Board) -> Hint {
+        let table = endgame::shared(board);
+        let mut evaluations = Vec::new();
+        for pos in board.list_can_sow() {
+            let mut next = board.clone();
+            next.sow(pos);
+            let v = if next.side == board.side {
+                Self::negamax(&table, &next, self.depth - 1, i32::MIN + 1, i32::MAX)
+            } else {
+                -Self::negamax(&table, &next, self.depth - 1, i32::MIN + 1, i32::MAX)
+            };
+            evaluations.push(PitEvaluation {
+                pit: pos as u32,
+                score: v,
+            });
+        }
+        let mut turn = Vec::new();
+        if let Some(best) = evaluations.iter().max_by_key(|e| e.score) {
+            let mut next = board.clone();
+            next.sow(best.pit as usize);
+            turn.push(best.pit as usize);
+            while next.side == board.side && !next.is_finished() {
+                let pos = self.choose(&table, &next);
+                next.sow(pos);
+                turn.push(pos);
+            }
+        }
+        Hint { evaluations, turn }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct PitEvaluation {
+    pub pit: u32,
+    /// Store of the side to move minus that of the opponent at the end of the
+    /// searched depth, or the exact final difference where the game or the endgame
+    /// table is reached first.
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Hint {
+    pub evaluations: Vec<PitEvaluation>,
+    pub turn: Vec<usize>,
+}
+
+impl<R: Rng> AI for DfsAI<R> {
+    fn sow(&mut self, board: &Board) -> Vec<usize> {
+        let mut board = board.clone();
+        let side = board.side;
+        let mut turn = Vec::new();
+        let table = endgame::shared(&board);
+        while board.side == side && !board.is_finished() {
+            let pos = self.choose(&table, &board);
+            board.sow(pos);
+            turn.push(pos);
+        }
+        turn
+    }
+}
+
+fn parse_param<T: FromStr>(name: &str, value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| format!("invalid {} {:?}: {}", name, value, e))
+}
+
+/// A time limit of MCTS, which never expands its tree without one.
+fn parse_limit(value: &str) -> Result<u64, String> {
+    let limit = parse_param("limit", value)?;
+    if limit == 0 {
+        return Err("limit must be positive".to_owned());
+    }
+    Ok(limit)
+}
+
+/// Builds a Kalah AI from `"random"`, `"dfs:<depth>"` or
+/// `"mcts:<limit ms>:<c>[:<seed>]"`.
+pub fn build_ai(searcher: &str) -> Result<Box<dyn AI>, String> {
+    build_ai_with_rng(searcher, SmallRng::from_os_rng())
+}
+
+/// [`build_ai`] with random choices drawn from `seed` unless the searcher names
+/// its own, for reproducible matches. Time-limited searchers still depend on the
+/// speed of the machine.
+pub fn build_seeded_ai(searcher: &str, seed: u64) -> Result<Box<dyn AI>, String> {
+    build_ai_with_rng(searcher, SmallRng::seed_from_u64(seed))
+}
+
+fn build_ai_with_rng(searcher: &str, rng: SmallRng) -> Result<Box<dyn AI>, String> {
+    let words = searcher.split(':').collect::<Vec<_>>();
+    match words[..] {
+        ["random"] => Ok(Box::new(RandomAI::new(rng))),
+        ["dfs", depth] => {
+            let depth = parse_param("depth", depth)?;
+            if depth == 0 {
+                return Err("depth must be positive".to_owned());
+            }
+            Ok(Box::new(DfsAI::new(rng, depth)))
+        }
+        ["mcts", limit, c] => Ok(Box::new(McTreeAI::new(
+            rng,
+            parse_limit(limit)?,
+            parse_param("c", c)?,
+        ))),
+        ["mcts", limit, c, seed] => Ok(Box::new(McTreeAI::new(
+            SmallRng::seed_from_u64(parse_param("seed", seed)?),
+            parse_limit(limit)?,
+            parse_param("c", c)?,
+        ))),
+        _ => Err(format!("unknown searcher: {}", searcher)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_pcg::Pcg32;
+
+    fn board(side: Side, seeds: [Vec<u8>; 2], score: [u8; 2]) -> Board {
+        Board {
+            side,
+            stealing: true,
+            seeds,
+            score,
+        }
+    }
+
+    #[test]
+    fn new_checks_range() {
+        assert!(Board::new(3, 4, true).is_err());
+        assert!(Board::new(9, 4, true).is_err());
+        assert!(Board::new(6, 2, true).is_err());
+        assert!(Board::new(6, 7, true).is_err());
+        for pits in PITS {
+            for seeds in SEEDS {
+                let b = Board::new(pits, seeds, false).unwrap();
+                assert!(b.validate().is_ok());
+                assert_eq!(b.total_seeds() as usize, pits * seeds as usize * 2);
+            }
+        }
+        assert!(Board::new(6, 4, true).unwrap().is_standard());
+        assert!(!Board::new(6, 6, true).unwrap().is_standard());
+    }
+
+    #[test]
+    fn extra_turn() {
+        let mut b = Board::new(4, 3, true).unwrap();
+        b.sow(1);
+        assert_eq!(b.seeds, [vec![3, 0, 4, 4], vec![3, 3, 3, 3]]);
+        assert_eq!(b.score, [1, 0]);
+        assert_eq!(b.side, Side::First);
+    }
+
+    #[test]
+    fn skip_opponent_store() {
+        let mut b = board(Side::First, [vec![0, 0, 0, 10], vec![1; 4]], [0, 0]);
+        b.sow(3);
+        assert_eq!(b.seeds, [vec![1, 1, 1, 1], vec![2; 4]]);
+        assert_eq!(b.score, [2, 0]);
+        assert_eq!(b.side, Side::First);
+    }
+
+    #[test]
+    fn steal() {
+        let mut b = board(
+            Side::First,
+            [vec![1, 0, 0, 0, 2], vec![1, 2, 3, 4, 5]],
+            [0, 0],
+        );
+        b.sow(0);
+        assert_eq!(b.seeds, [vec![0, 0, 0, 0, 2], vec![1, 2, 3, 0, 5]]);
+        assert_eq!(b.score, [5, 0]);
+
+        let mut b = board(
+            Side::First,
+            [vec![1, 0, 0, 0, 2], vec![1, 2, 3, 4, 5]],
+            [0, 0],
+        );
+        b.stealing = false;
+        b.sow(0);
+        assert_eq!(b.seeds, [vec![0, 1, 0, 0, 2], vec![1, 2, 3, 4, 5]]);
+        assert_eq!(b.score, [0, 0]);
+    }
+
+    #[test]
+    fn finish() {
+        let mut b = board(Side::First, [vec![0, 0, 0, 1], vec![1, 2, 3, 4]], [10, 3]);
+        b.sow(3);
+        assert!(b.is_finished());
+        assert_eq!(b.last_scores(), (11, 13));
+    }
+
+    #[test]
+    fn game_trait() {
+        let mut b = board(Side::First, [vec![0, 0, 2, 1], vec![1, 2, 3, 4]], [10, 3]);
+        assert_eq!(Game::legal_moves(&b), vec![2, 3]);
+        b.apply(3);
+        assert_eq!(b.to_move(), Player::First);
+        assert_eq!(b.result(), None);
+        b.apply(2);
+        assert_eq!(b.to_move(), Player::First);
+        assert_eq!(Game::legal_moves(&b), vec![3]);
+        assert_eq!(game::perft(&b, 1), 1);
+
+        let mut b = board(Side::First, [vec![0, 0, 0, 1], vec![1, 2, 3, 4]], [10, 3]);
+        b.apply(3);
+        assert_eq!(b.result(), Some(GameResult::Win(Player::Second)));
+        assert!(Game::legal_moves(&b).is_empty());
+    }
+
+    #[test]
+    fn smoke() {
+        for (seed, (pits, seeds)) in [(4, 3), (5, 6), (6, 6), (8, 3)].into_iter().enumerate() {
+            let mut ai: [Box<dyn AI>; 2] = [
+                Box::new(DfsAI::new(Pcg32::seed_from_u64(seed as u64), 4)),
+                Box::new(RandomAI::new(Pcg32::seed_from_u64(seed as u64))),
+            ];
+            let mut b = Board::new(pits, seeds, seed % 2 == 0).unwrap();
+            let total = b.total_seeds();
+            while !b.is_finished() {
+                let side = b.side;
+                let turn = ai[side.index()].sow(&b);
+                assert!(!turn.is_empty());
+                for pos in turn {
+                    assert_eq!(b.side, side);
+                    assert!(b.can_sow(pos).is_ok());
+                    b.sow(pos);
+                }
+                assert!(b.side != side || b.is_finished());
+                assert_eq!(b.total_seeds(), total);
+            }
+        }
+    }
+
+    #[test]
+    fn hint() {
+        let board = Board::from_parts(
+            Side::First,
+            false,
+            [vec![0, 0, 2, 1], vec![3, 3, 3, 0]],
+            [7, 0],
+        )
+        .unwrap();
+        let mut ai = DfsAI::new(Pcg32::seed_from_u64(0), 8);
+        let hint = ai.hint(&board);
+        assert_eq!(
+            hint.evaluations,
+            vec![
+                PitEvaluation { pit: 2, score: -1 },
+                PitEvaluation { pit: 3, score: 1 },
+            ]
+        );
+        assert_eq!(hint.turn, vec![3, 2, 3]);
+    }
+
+    #[test]
+    fn parse_ai() {
+        assert!(build_ai("random").is_ok());
+        assert!(build_ai("dfs:6").is_ok());
+        assert!(build_ai("dfs:0").is_err());
+        assert!(build_ai("dfs:nn6:3").is_err());
+        assert!(build_ai("mcts:100:1.4").is_ok());
+        assert!(build_ai("mcts:100:1.4:42").is_ok());
+        assert!(build_ai("mcts:100").is_err());
+        assert!(build_ai("mcts:100:c").is_err());
+        assert!(build_ai("mcts:0:1.4").is_err());
+        assert!(build_seeded_ai("mcts:0:1.4:42", 1).is_err());
+    }
+}
@@ -1,11 +1,38 @@
+pub mod endgame;
+pub mod kalah;
+mod mctree;
+pub mod oware;
+mod side;
+
+use game::{
+    analysis::{Annotation, Evaluation, Thresholds, analyze},
+    history::History,
+    record::GameRecord,
+};
 use mancala_rust::{Board, build_ai};
+use rand::{SeedableRng, rngs::SmallRng};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_wasm_bindgen::{from_value, to_value};
 use wasm_bindgen::prelude::*;
 
-fn parse_board(board: &JsValue) -> Result<Board, JsValue> {
+pub use crate::side::Side;
+
+fn parse_board<B: DeserializeOwned>(board: &JsValue) -> Result<B, JsValue> {
     from_value(board.clone()).map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
+fn parse_kalah_board(board: &JsValue) -> Result<kalah::Board, JsValue> {
+    let board: kalah::Board = parse_board(board)?;
+    board.validate().map_err(|e| JsValue::from_str(&e))?;
+    Ok(board)
+}
+
+fn parse_oware_board(board: &JsValue) -> Result<oware::Board, JsValue> {
+    let board: oware::Board = parse_board(board)?;
+    board.validate().map_err(|e| JsValue::from_str(&e))?;
+    Ok(board)
+}
+
 fn calculate_score(board: &Board) -> Option<(u8, u8)> {
     if board.is_finished() {
         Some(board.last_scores())
@@ -14,9 +41,21 @@ fn calculate_score(board: &Board) -> Option<(u8, u8)> {
     }
 }
 
+fn kalah_calculate_score(board: &kalah::Board) -> Option<(u8, u8)> {
+    if board.is_finished() {
+        Some(board.last_scores())
+    } else {
+        None
+    }
+}
+
 #[wasm_bindgen(js_name = calculateScore)]
 pub fn js_calculate_score(board: &JsValue) -> Result<JsValue, JsValue> {
-    let board = parse_board(board)?;
+    let kalah = parse_kalah_board(board)?;
+    if !kalah.is_standard() {
+        return Ok(to_value(&kalah_calculate_score(&kalah))?);
+    }
+    let board: Board = parse_board(board)?;
     let scores = calculate_score(&board);
     Ok(to_value(&scores)?)
 }
@@ -30,9 +69,24 @@ fn calculate_moved(board: &Board, pos: &[usize]) -> Result<Board, String> {
     Ok(board)
 }
 
+fn kalah_calculate_moved(board: &kalah::Board, pos: &[usize]) -> Result<kalah::Board, String> {
+    let mut board = board.clone();
+    for &pos in pos {
+        board.can_sow(pos)?;
+        board.sow(pos);
+    }
+    Ok(board)
+}
+
 #[wasm_bindgen(js_name = calculateMoved)]
 pub fn js_calculate_moved(board: &JsValue, pos: u32) -> Result<JsValue, JsValue> {
-    let board = parse_board(board)?;
+    let kalah = parse_kalah_board(board)?;
+    if !kalah.is_standard() {
+        let board =
+            kalah_calculate_moved(&kalah, &[pos as usize]).map_err(|e| JsValue::from_str(&e))?;
+        return Ok(to_value(&board)?);
+    }
+    let board: Board = parse_board(board)?;
     let board = calculate_moved(&board, &[pos as usize]).map_err(|e| JsValue::from_str(&e))?;
     Ok(to_value(&board)?)
 }
@@ -43,13 +97,359 @@ fn search(board: &Board, searcher: &str) -> Result<Board, String> {
     Ok(calculate_moved(board, &pos).unwrap())
 }
 
+fn kalah_search(board: &kalah::Board, searcher: &str) -> Result<kalah::Board, String> {
+    if board.is_finished() {
+        return Err("game is finished".to_owned());
+    }
+    let mut searcher = kalah::build_ai(searcher)?;
+    let pos = searcher.sow(board);
+    kalah_calculate_moved(board, &pos)
+}
+
+/// The full turn of perfect play on a board covered by the endgame table, for
+/// the searchers of `mancala_rust`, which know nothing of it.
+fn endgame_turn(board: &kalah::Board) -> Option<Vec<usize>> {
+    let table = endgame::shared(board);
+    let mut board = board.clone();
+    let side = board.side();
+    let mut turn = Vec::new();
+    // Sowing only takes seeds off the board, so the rest of the turn stays covered.
+    while board.side() == side && !board.is_finished() {
+        let pos = table.best_move(&board)?;
+        board.sow(pos);
+        turn.push(pos);
+    }
+    Some(turn)
+}
+
+/// Whether `searcher` is a player of [`kalah`] on `board`. Only the evaluator
+/// searchers of `mancala_rust`, such as `dfs:nn6:3`, play the standard 6-pit,
+/// 4-seed shape instead, so that `dfs:<depth>` is the player of [`hint`] on
+/// every board.
+fn plays_kalah(board: &kalah::Board, searcher: &str) -> bool {
+    !board.is_standard() || kalah::build_ai(searcher).is_ok()
+}
+
+/// Whether `searcher` names a searcher of `mancala_rust`.
+pub fn is_mancala_rust_searcher(searcher: &str) -> bool {
+    build_ai(true, searcher).is_ok()
+}
+
+/// Players of [`kalah`] as split by [`plays_kalah`], or else those of
+/// `mancala_rust`, which play perfectly once the endgame table covers the board.
 #[wasm_bindgen(js_name = search)]
 pub fn js_search(board: &JsValue, searcher: &str) -> Result<JsValue, JsValue> {
-    let board = parse_board(board)?;
+    let kalah = parse_kalah_board(board)?;
+    if plays_kalah(&kalah, searcher) {
+        let board = kalah_search(&kalah, searcher).map_err(|e| JsValue::from_str(&e))?;
+        return Ok(to_value(&board)?);
+    }
+    if let Some(turn) = endgame_turn(&kalah) {
+        let board = kalah_calculate_moved(&kalah, &turn).map_err(|e| JsValue::from_str(&e))?;
+        return Ok(to_value(&board)?);
+    }
+    let board: Board = parse_board(board)?;
     let board = search(&board, searcher).map_err(|e| JsValue::from_str(&e))?;
     Ok(to_value(&board)?)
 }
 
+/// The pits of one full turn chosen by `searcher`, with the same split between
+/// `mancala_rust` and [`kalah`] players as [`js_search`].
+pub fn search_turn(board: &kalah::Board, searcher: &str) -> Result<Vec<usize>, String> {
+    if board.is_finished() {
+        return Err("game is finished".to_owned());
+    }
+    if plays_kalah(board, searcher) {
+        return Ok(kalah::build_ai(searcher)?.sow(board));
+    }
+    if let Some(turn) = endgame_turn(board) {
+        return Ok(turn);
+    }
+    let value = serde_json::to_value(board).map_err(|e| e.to_string())?;
+    let board: Board = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    let mut searcher = build_ai(board.stealing(), searcher)?;
+    Ok(searcher.sow(&board))
+}
+
+fn hint(board: &kalah::Board, depth: u32) -> Result<kalah::Hint, String> {
+    if board.is_finished() {
+        return Err("game is finished".to_owned());
+    }
+    if depth == 0 {
+        return Err("depth must be positive".to_owned());
+    }
+    let mut ai = kalah::DfsAI::new(SmallRng::from_os_rng(), depth);
+    Ok(ai.hint(board))
+}
+
+/// Evaluation of every legal pit for the side to move and the best full turn, as
+/// searched by the `dfs:<depth>` player of [`kalah`] on any board shape.
+#[wasm_bindgen(js_name = hint)]
+pub fn js_hint(board: &JsValue, depth: u32) -> Result<JsValue, JsValue> {
+    let board = parse_kalah_board(board)?;
+    let hint = hint(&board, depth).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&hint)?)
+}
+
+/// Builds the endgame table of boards with `pits` pits a row, which the first
+/// search or hint on such a board would otherwise build, taking seconds.
+#[wasm_bindgen(js_name = initEndgame)]
+pub fn js_init_endgame(pits: u32, stealing: bool) -> Result<(), JsValue> {
+    endgame::shared_shape(pits as usize, stealing).map_err(|e| JsValue::from_str(&e))?;
+    Ok(())
+}
+
+/// Exact final scores under perfect play when few enough seeds are left on the
+/// board, `null` otherwise.
+#[wasm_bindgen(js_name = endgameScore)]
+pub fn js_endgame_score(board: &JsValue) -> Result<JsValue, JsValue> {
+    let board = parse_kalah_board(board)?;
+    let scores = endgame::shared(&board).final_scores(&board);
+    Ok(to_value(&scores)?)
+}
+
+fn oware_calculate_score(board: &oware::Board) -> Option<(u8, u8)> {
+    if board.is_finished() {
+        Some(board.last_scores())
+    } else {
+        None
+    }
+}
+
+#[wasm_bindgen(js_name = owareCalculateScore)]
+pub fn js_oware_calculate_score(board: &JsValue) -> Result<JsValue, JsValue> {
+    let board = parse_oware_board(board)?;
+    let scores = oware_calculate_score(&board);
+    Ok(to_value(&scores)?)
+}
+
+fn oware_calculate_moved(board: &oware::Board, pos: usize) -> Result<oware::Board, String> {
+    let mut board = board.clone();
+    board.can_sow(pos)?;
+    board.sow(pos);
+    Ok(board)
+}
+
+#[wasm_bindgen(js_name = owareCalculateMoved)]
+pub fn js_oware_calculate_moved(board: &JsValue, pos: u32) -> Result<JsValue, JsValue> {
+    let board = parse_oware_board(board)?;
+    let board = oware_calculate_moved(&board, pos as usize).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&board)?)
+}
+
+fn oware_search(board: &oware::Board, searcher: &str) -> Result<oware::Board, String> {
+    if board.is_finished() {
+        return Err("game is finished".to_owned());
+    }
+    let mut searcher = oware::build_ai(searcher)?;
+    let pos = searcher.sow(board);
+    oware_calculate_moved(board, pos)
+}
+
+#[wasm_bindgen(js_name = owareSearch)]
+pub fn js_oware_search(board: &JsValue, searcher: &str) -> Result<JsValue, JsValue> {
+    let board = parse_oware_board(board)?;
+    let board = oware_search(&board, searcher).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&board)?)
+}
+
+/// Rules of a Kalah [`KalahRecord`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KalahVariant {
+    pub pits: usize,
+    pub seeds: u8,
+    pub stealing: bool,
+}
+
+/// A played game of Kalah, with the pit of each sowing. Extra turns are
+/// consecutive moves of the same side.
+pub type KalahRecord = GameRecord<KalahVariant, usize>;
+
+/// Rules of an Oware [`OwareRecord`]; only Abapa is played.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OwareVariant {}
+
+/// A played game of Oware, with the pit of each sowing.
+pub type OwareRecord = GameRecord<OwareVariant, usize>;
+
+fn kalah_start(variant: &KalahVariant) -> Result<kalah::Board, String> {
+    kalah::Board::new(variant.pits, variant.seeds, variant.stealing)
+}
+
+/// Reads a [`KalahRecord`], checking its moves and result.
+pub fn parse_kalah_record(json: &str) -> Result<KalahRecord, String> {
+    KalahRecord::parse(json, "kalah", kalah_start)
+}
+
+/// Writes a [`KalahRecord`] after the checks of [`parse_kalah_record`].
+pub fn emit_kalah_record(record: &KalahRecord) -> Result<String, String> {
+    record.emit("kalah", kalah_start)
+}
+
+/// Reads an [`OwareRecord`], checking its moves and result.
+pub fn parse_oware_record(json: &str) -> Result<OwareRecord, String> {
+    OwareRecord::parse(json, "oware", |_| Ok(oware::Board::new()))
+}
+
+/// Writes an [`OwareRecord`] after the checks of [`parse_oware_record`].
+pub fn emit_oware_record(record: &OwareRecord) -> Result<String, String> {
+    record.emit("oware", |_| Ok(oware::Board::new()))
+}
+
+#[wasm_bindgen(js_name = parseRecord)]
+pub fn js_parse_kalah_record(json: &str) -> Result<JsValue, JsValue> {
+    let record = parse_kalah_record(json).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&record)?)
+}
+
+#[wasm_bindgen(js_name = emitRecord)]
+pub fn js_emit_kalah_record(record: &JsValue) -> Result<String, JsValue> {
+    let record: KalahRecord = from_value(record.clone())?;
+    emit_kalah_record(&record).map_err(|e| JsValue::from_str(&e))
+}
+
+#[wasm_bindgen(js_name = owareParseRecord)]
+pub fn js_parse_oware_record(json: &str) -> Result<JsValue, JsValue> {
+    let record = parse_oware_record(json).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&record)?)
+}
+
+#[wasm_bindgen(js_name = owareEmitRecord)]
+pub fn js_emit_oware_record(record: &JsValue) -> Result<String, JsValue> {
+    let record: OwareRecord = from_value(record.clone())?;
+    emit_oware_record(&record).map_err(|e| JsValue::from_str(&e))
+}
+
+/// A game of Kalah from the start with its sowings, so that the frontend can
+/// undo, redo and jump to any ply without replaying them itself.
+#[wasm_bindgen]
+pub struct GameHistory {
+    history: History<kalah::Board>,
+}
+
+#[wasm_bindgen]
+impl GameHistory {
+    #[wasm_bindgen(constructor)]
+    pub fn new(pits: u32, seeds: u8, stealing: bool) -> Result<GameHistory, JsValue> {
+        let board =
+            kalah::Board::new(pits as usize, seeds, stealing).map_err(|e| JsValue::from_str(&e))?;
+        Ok(GameHistory {
+            history: History::new(board),
+        })
+    }
+
+    /// The current board in the form taken by `hint`.
+    pub fn board(&self) -> Result<JsValue, JsValue> {
+        Ok(to_value(self.history.current())?)
+    }
+}
+
+game::history_methods! {
+    GameHistory,
+    /// Sows `pos` of the side to move; an extra turn leaves the side as it is.
+    play(pos)
+}
+
+/// A [`GameHistory`] of Oware.
+#[wasm_bindgen]
+pub struct OwareGameHistory {
+    history: History<oware::Board>,
+}
+
+impl Default for OwareGameHistory {
+    fn default() -> OwareGameHistory {
+        OwareGameHistory::new()
+    }
+}
+
+#[wasm_bindgen]
+impl OwareGameHistory {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> OwareGameHistory {
+        OwareGameHistory {
+            history: History::new(oware::Board::new()),
+        }
+    }
+
+    /// The current board in the form taken by `owareSearch`.
+    pub fn board(&self) -> Result<JsValue, JsValue> {
+        Ok(to_value(self.history.current())?)
+    }
+}
+
+game::history_methods! {
+    OwareGameHistory,
+    /// Sows `pos` of the side to move.
+    play(pos)
+}
+
+/// Expected point of a final lead of `seeds`, on a logistic curve where a lead
+/// of four seeds is worth about 0.73.
+fn seed_point(seeds: i32) -> f64 {
+    1.0 / (1.0 + (-seeds as f64 / 4.0).exp())
+}
+
+/// Annotates every sowing of `record` by the `dfs:<depth>` player, exact where
+/// the endgame table covers the position.
+pub fn analyze_kalah_game(
+    record: &KalahRecord,
+    depth: u32,
+) -> Result<Vec<Annotation<usize>>, String> {
+    if depth == 0 {
+        return Err("depth must be positive".to_owned());
+    }
+    let mut ai = kalah::DfsAI::new(SmallRng::seed_from_u64(0), depth);
+    analyze(
+        &kalah_start(&record.variant)?,
+        &record.move_list(),
+        |board| {
+            let hint = ai.hint(board);
+            Evaluation {
+                point: seed_point(hint.evaluations.iter().map(|e| e.score).max().unwrap()),
+                best: hint.turn.first().copied(),
+            }
+        },
+        &Thresholds::default(),
+    )
+}
+
+/// Annotates every sowing of `record` by the `dfs:<depth>` player of Oware.
+pub fn analyze_oware_game(
+    record: &OwareRecord,
+    depth: u32,
+) -> Result<Vec<Annotation<usize>>, String> {
+    if depth == 0 {
+        return Err("depth must be positive".to_owned());
+    }
+    let ai = oware::DfsAI::new(SmallRng::seed_from_u64(0), depth);
+    analyze(
+        &oware::Board::new(),
+        &record.move_list(),
+        |board| {
+            let (pos, score) = ai.best(board).unwrap();
+            Evaluation {
+                point: seed_point(score),
+                best: Some(pos),
+            }
+        },
+        &Thresholds::default(),
+    )
+}
+
+#[wasm_bindgen(js_name = analyzeGame)]
+pub fn js_analyze_kalah_game(record: &JsValue, depth: u32) -> Result<JsValue, JsValue> {
+    let record: KalahRecord = from_value(record.clone())?;
+    let annotations = analyze_kalah_game(&record, depth).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&annotations)?)
+}
+
+#[wasm_bindgen(js_name = owareAnalyzeGame)]
+pub fn js_analyze_oware_game(record: &JsValue, depth: u32) -> Result<JsValue, JsValue> {
+    let record: OwareRecord = from_value(record.clone())?;
+    let annotations = analyze_oware_game(&record, depth).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&annotations)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,6 +465,141 @@ mod tests {
         // Note: We can't test the JS functions directly in regular tests
         // as they require wasm-bindgen runtime
     }
+
+    #[test]
+    fn test_kalah_search() {
+        let board = kalah::Board::new(5, 3, true).unwrap();
+        assert_eq!(kalah_calculate_score(&board), None);
+        assert!(kalah_calculate_moved(&board, &[5]).is_err());
+        let moved = kalah_search(&board, "dfs:3").unwrap();
+        assert_eq!(moved.side(), Side::Second);
+        assert!(kalah_search(&board, "dfs:nn6:3").is_err());
+        let moved = kalah_search(&board, "mcts:10:1.4:1").unwrap();
+        assert_eq!(moved.side(), Side::Second);
+    }
+
+    #[test]
+    fn test_search_turn() {
+        let board = kalah::Board::new(6, 4, true).unwrap();
+        let turn = search_turn(&board, "dfs:2").unwrap();
+        assert!(kalah_calculate_moved(&board, &turn).is_ok());
+        let board = kalah::Board::new(4, 3, true).unwrap();
+        let turn = search_turn(&board, "dfs:2").unwrap();
+        assert_eq!(
+            kalah_calculate_moved(&board, &turn).unwrap().side(),
+            Side::Second
+        );
+        assert!(search_turn(&board, "unknown").is_err());
+
+        // A standard board with eight seeds left is played from the endgame table,
+        // which wins 25 to 23 where sowing the first pit loses 21 to 27.
+        let board = kalah::Board::from_parts(
+            Side::First,
+            true,
+            [vec![1, 2, 0, 0, 0, 1], vec![4, 0, 0, 0, 0, 0]],
+            [20, 20],
+        )
+        .unwrap();
+        assert!(board.is_standard());
+        let table = endgame::shared(&board);
+        let turn = search_turn(&board, "dfs:nn6:3").unwrap();
+        let after = kalah_calculate_moved(&board, &turn).unwrap();
+        assert_eq!(after.side(), Side::Second);
+        assert_eq!(table.final_scores(&after), Some((25, 23)));
+    }
+
+    #[test]
+    fn test_hint() {
+        let board = kalah::Board::new(6, 4, true).unwrap();
+        assert!(hint(&board, 0).is_err());
+        let r = hint(&board, 4).unwrap();
+        assert_eq!(r.evaluations.len(), 6);
+        assert!(!r.turn.is_empty());
+        assert!(kalah_calculate_moved(&board, &r.turn).is_ok());
+
+        // The CPU of the same depth is the same engine on the standard board, so
+        // its first pit is one the hint rates best.
+        assert!(plays_kalah(&board, "dfs:4"));
+        assert!(!plays_kalah(&board, "dfs:nn6:3"));
+        let best = r.evaluations.iter().map(|e| e.score).max().unwrap();
+        let pit = search_turn(&board, "dfs:4").unwrap()[0] as u32;
+        let rated = r.evaluations.iter().find(|e| e.pit == pit).unwrap();
+        assert_eq!(rated.score, best);
+    }
+
+    #[test]
+    fn test_oware_search() {
+        let board = oware::Board::new();
+        assert_eq!(oware_calculate_score(&board), None);
+        assert!(oware_calculate_moved(&board, 6).is_err());
+        let moved = oware_search(&board, "dfs:3").unwrap();
+        assert_eq!(moved.side(), Side::Second);
+        assert!(oware_search(&board, "unknown").is_err());
+    }
+
+    #[test]
+    fn records() {
+        let variant = KalahVariant {
+            pits: 4,
+            seeds: 4,
+            stealing: true,
+        };
+        let mut record = KalahRecord::new("kalah", variant.clone());
+        let mut board = kalah_start(&variant).unwrap();
+        // The four seeds of the first pit end in the store for an extra turn.
+        for pos in [0, 1] {
+            board.sow(pos);
+            record.push(&board, pos, None);
+        }
+        assert_eq!(board.side(), Side::Second);
+        let json = emit_kalah_record(&record).unwrap();
+        assert_eq!(parse_kalah_record(&json).unwrap(), record);
+        assert!(parse_oware_record(&json).is_err());
+
+        let mut record = OwareRecord::new("oware", OwareVariant {});
+        let mut board = oware::Board::new();
+        board.sow(0);
+        record.push(&board, 0, Some(0.5));
+        let json = emit_oware_record(&record).unwrap();
+        assert_eq!(parse_oware_record(&json).unwrap(), record);
+        record.moves[0].mv = 6;
+        assert!(emit_oware_record(&record).is_err());
+    }
+
+    #[test]
+    fn analysis() {
+        let variant = KalahVariant {
+            pits: 4,
+            seeds: 3,
+            stealing: true,
+        };
+        let mut record = KalahRecord::new("kalah", variant.clone());
+        let mut board = kalah_start(&variant).unwrap();
+        for pos in [1, 0, 2] {
+            board.sow(pos);
+            record.push(&board, pos, None);
+        }
+        let annotations = analyze_kalah_game(&record, 6).unwrap();
+        assert_eq!(annotations.len(), 3);
+        // The three seeds of the second pit end in the store.
+        assert_eq!(annotations[1].player, game::Player::First);
+        for a in &annotations {
+            assert!((0.0..=1.0).contains(&a.before) && (0.0..=1.0).contains(&a.after));
+        }
+        assert!(analyze_kalah_game(&record, 0).is_err());
+        assert_eq!(seed_point(0), 0.5);
+        assert!(seed_point(4) > 0.7 && seed_point(-4) < 0.3);
+
+        let mut record = OwareRecord::new("oware", OwareVariant {});
+        let mut board = oware::Board::new();
+        for pos in [0, 0, 1] {
+            board.sow(pos);
+            record.push(&board, pos, None);
+        }
+        let annotations = analyze_oware_game(&record, 3).unwrap();
+        assert_eq!(annotations[1].player, game::Player::Second);
+        assert!(annotations.iter().all(|a| a.best.is_some()));
+    }
 }
 
 #[cfg(all(target_arch = "wasm32", test))]
@@ -100,4 +635,23 @@ mod wasm_tests {
             .is_ok()
         );
     }
+
+    #[wasm_bindgen_test]
+    fn history() {
+        let mut history = GameHistory::new(6, 4, true).unwrap();
+        // An extra turn, then the same side again.
+        history.play(2).unwrap();
+        history.play(0).unwrap();
+        assert!(history.play(6).is_err());
+        history.jump_to(1).unwrap();
+        let board: kalah::Board = from_value(history.board().unwrap()).unwrap();
+        assert_eq!(board.side(), Side::First);
+        assert!(history.redo());
+        history.status().unwrap();
+
+        let mut history = OwareGameHistory::new();
+        history.play(0).unwrap();
+        assert!(history.undo());
+        history.status().unwrap();
+    }
 }
@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use game::{
+    GameResult,
+    mcts::{Mcts, Playout},
+    time::Instant,
+};
+use rand::{Rng, seq::IndexedRandom};
+
+use crate::{
+    endgame::{self, EndgameTable},
+    kalah::{AI, Board},
+    side::final_result,
+};
+
+/// Random sowings until the game ends or the position is covered by the
+/// [endgame table](crate::endgame), which scores it exactly.
+struct EndgamePlayout<'a, R> {
+    rng: &'a mut R,
+    endgame: &'a EndgameTable,
+}
+
+impl<R: Rng> Playout<Board> for EndgamePlayout<'_, R> {
+    fn playout(&mut self, board: &Board) -> GameResult {
+        let mut board = board.clone();
+        loop {
+            if let Some(scores) = self.endgame.final_scores(&board) {
+                return final_result(scores);
+            }
+            if board.is_finished() {
+                return final_result(board.last_scores());
+            }
+            let pos = *board.list_can_sow().choose(self.rng).expect("no next");
+            board.sow(pos);
+        }
+    }
+}
+
+/// Visits a root needs before a pick is made from it without searching again.
+const MIN_VISITS: u32 = 1000;
+
+/// UCT player for Kalah on the generic engine of [`game::mcts`].
+///
+/// The engine keeps the player to move in every node, so a sowing that earns an
+/// extra turn leads to a node where the same side chooses again, and the subtree
+/// of that node is reused for the rest of the turn. A subtree of fewer than
+/// [`MIN_VISITS`] visits is searched again, for the time limit and at least
+/// until it has that many.
+pub struct McTreeAI<R> {
+    rng: R,
+    limit: Duration,
+    c: f64,
+    min_visits: u32,
+}
+
+impl<R: Rng> McTreeAI<R> {
+    pub fn new(rng: R, limit: u64, c: f64) -> McTreeAI<R> {
+        McTreeAI {
+            rng,
+            limit: Duration::from_millis(limit),
+            c,
+            min_visits: MIN_VISITS,
+        }
+    }
+
+    /// The pits of a turn, each with the visits of the root it was picked from.
+    fn turn(&mut self, board: &Board) -> Vec<(usize, u32)> {
+        let side = board.side();
+        let endgame = endgame::shared(board);
+        let playout = EndgamePlayout {
+            rng: &mut self.rng,
+            endgame: &endgame,
+        };
+        let mut mcts = Mcts::new(board, playout, self.c);
+        let mut turn = Vec::new();
+        while mcts.root().side() == side && !mcts.root().is_finished() {
+            let pos = match endgame.best_move(mcts.root()) {
+                Some(pos) => pos,
+                None => {
+                    if mcts.visits() < self.min_visits {
+                        let start = Instant::now();
+                        while (start.elapsed() < self.limit || mcts.visits() < self.min_visits)
+                            && mcts.proof().is_none()
+                        {
+                            mcts.run(100);
+                        }
+                    }
+                    mcts.best().expect("No children").mv
+                }
+            };
+            turn.push((pos, mcts.visits()));
+            mcts.advance(pos);
+        }
+        turn
+    }
+}
+
+impl<R: Rng> AI for McTreeAI<R> {
+    fn sow(&mut self, board: &Board) -> Vec<usize> {
+        self.turn(board).into_iter().map(|(pos, _)| pos).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Side;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg32;
+
+    #[test]
+    fn take_extra_turn() {
+        // 3, 2, 3 all end in the store and win 10 to 9, while starting with 2
+        // loses 9 to 10. Too many seeds are left for the endgame table.
+        let board = Board::from_parts(
+            Side::First,
+            false,
+            [vec![0, 0, 2, 1], vec![3, 3, 3, 0]],
+            [7, 0],
+        )
+        .unwrap();
+        let mut ai = McTreeAI::new(Pcg32::seed_from_u64(1), 50, 1.4);
+        assert_eq!(ai.sow(&board), vec![3, 2, 3]);
+    }
+
+    #[test]
+    fn search_again_in_turn() {
+        // Pit 5 ends in the store, and then so does pit 2. The subtree behind the
+        // first pick holds only part of the 300 visits of the root.
+        let board = Board::from_parts(
+            Side::First,
+            true,
+            [vec![4, 4, 4, 4, 4, 1], vec![4, 4, 4, 4, 4, 4]],
+            [0, 0],
+        )
+        .unwrap();
+        let mut ai = McTreeAI {
+            min_visits: 300,
+            ..McTreeAI::new(Pcg32::seed_from_u64(3), 0, 1.4)
+        };
+        let turn = ai.turn(&board);
+        assert!(turn.len() >= 2, "{:?}", turn);
+        for &(_, visits) in &turn {
+            assert!(visits >= 300, "{:?}", turn);
+        }
+    }
+
+    #[test]
+    fn smoke() {
+        let mut ai = McTreeAI::new(Pcg32::seed_from_u64(7), 5, 1.4);
+        let mut b = Board::new(5, 3, false).unwrap();
+        while !b.is_finished() {
+            let side = b.side();
+            for pos in ai.sow(&b) {
+                assert_eq!(b.side(), side);
+                assert!(b.can_sow(pos).is_ok());
+                b.sow(pos);
+            }
+        }
+    }
+}
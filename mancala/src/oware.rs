@@ -0,0 +1,517 @@
+//! Oware (Abapa rule set).
+//!
+//! Each side owns a row of six pits; there is no store to sow into. Seeds are
+//! sown counter-clockwise, skipping the origin pit when it held 12 or more.
+//! If the last seed lands in the opponent's row and makes 2 or 3 there, that
+//! pit and the consecutive 2s and 3s before it are captured, unless the
+//! capture would take every seed of the opponent (grand slam), in which case
+//! nothing is captured. A player must leave the opponent something to play;
+//! when that is impossible the game ends and each side keeps its own row. So
+//! does a game that goes [`MAX_QUIET_PLIES`] plies without a capture, which
+//! stands for the endless cycles that Abapa players agree to end.
+use game::{Game, GameResult, Player, hash_of};
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::IndexedRandom, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::{Side, side::final_result};
+
+pub const PITS: usize = 6;
+const INITIAL_SEEDS: u8 = 4;
+const TOTAL_SEEDS: u8 = INITIAL_SEEDS * PITS as u8 * 2;
+/// Plies without a capture after which the game ends in a cycle.
+pub const MAX_QUIET_PLIES: u16 = 200;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Board {
+    side: Side,
+    seeds: [[u8; PITS]; 2],
+    score: [u8; 2],
+    /// Plies since the last capture, or since the start.
+    #[serde(default)]
+    quiet_plies: u16,
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+impl Board {
+    pub fn new() -> Board {
+        Board {
+            side: Side::First,
+            seeds: [[INITIAL_SEEDS; PITS]; 2],
+            score: [0, 0],
+            quiet_plies: 0,
+        }
+    }
+
+    /// Checks a board that came from outside, e.g. through serde: all 48 seeds
+    /// are on the board or captured, and the side to move has seeds, as no move
+    /// can empty the row of the player who moves next.
+    pub fn validate(&self) -> Result<(), String> {
+        let total = self
+            .seeds
+            .iter()
+            .flatten()
+            .map(|&s| s as usize)
+            .sum::<usize>()
+            + self.score.iter().map(|&s| s as usize).sum::<usize>();
+        if total != TOTAL_SEEDS as usize {
+            return Err(format!(
+                "seeds must add up to {}, not {}",
+                TOTAL_SEEDS, total
+            ));
+        }
+        if self.seeds[self.side.index()].iter().all(|&s| s == 0) {
+            return Err("the side to move has no seeds".to_owned());
+        }
+        if self.quiet_plies > MAX_QUIET_PLIES {
+            return Err(format!("quietPlies must be at most {}", MAX_QUIET_PLIES));
+        }
+        Ok(())
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    pub fn seeds(&self) -> &[[u8; PITS]; 2] {
+        &self.seeds
+    }
+
+    pub fn score(&self) -> [u8; 2] {
+        self.score
+    }
+
+    pub fn quiet_plies(&self) -> u16 {
+        self.quiet_plies
+    }
+
+    fn row_sum(&self, side: Side) -> u8 {
+        self.seeds[side.index()].iter().sum()
+    }
+
+    /// Distributes the seeds of `pos` and returns the pit the last seed fell in.
+    fn scatter(&mut self, pos: usize) -> (Side, usize) {
+        let me = self.side;
+        let mut n = self.seeds[me.index()][pos];
+        self.seeds[me.index()][pos] = 0;
+        let (mut side, mut idx) = (me, pos);
+        while n > 0 {
+            idx += 1;
+            if idx == PITS {
+                idx = 0;
+                side = side.flip();
+            }
+            if side == me && idx == pos {
+                continue;
+            }
+            self.seeds[side.index()][idx] += 1;
+            n -= 1;
+        }
+        (side, idx)
+    }
+
+    pub fn can_sow(&self, pos: usize) -> Result<(), String> {
+        if pos >= PITS {
+            return Err(format!("pit {} is out of range", pos));
+        }
+        if self.seeds[self.side.index()][pos] == 0 {
+            return Err(format!("pit {} is empty", pos));
+        }
+        if self.row_sum(self.side.flip()) == 0 {
+            let mut board = self.clone();
+            board.scatter(pos);
+            if board.row_sum(self.side.flip()) == 0 {
+                return Err(format!("pit {} does not feed the opponent", pos));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn list_can_sow(&self) -> Vec<usize> {
+        (0..PITS).filter(|&pos| self.can_sow(pos).is_ok()).collect()
+    }
+
+    /// Plays `pos` for the side to move. The move must satisfy [`Board::can_sow`].
+    pub fn sow(&mut self, pos: usize) {
+        debug_assert!(self.can_sow(pos).is_ok());
+        let me = self.side;
+        let opp = me.flip();
+        let (last_side, last) = self.scatter(pos);
+        self.quiet_plies = self.quiet_plies.saturating_add(1);
+        if last_side == opp {
+            let row = &self.seeds[opp.index()];
+            let mut captured = 0;
+            let mut first = last + 1;
+            while first > 0 && matches!(row[first - 1], 2 | 3) {
+                first -= 1;
+                captured += row[first];
+            }
+            // Grand slam: a capture that would empty the opponent's row takes nothing.
+            if captured > 0 && captured < self.row_sum(opp) {
+                for idx in first..=last {
+                    self.seeds[opp.index()][idx] = 0;
+                }
+                self.score[me.index()] += captured;
+                self.quiet_plies = 0;
+            }
+        }
+        self.side = opp;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let half = TOTAL_SEEDS / 2;
+        self.score[0] > half
+            || self.score[1] > half
+            || (self.score[0] == half && self.score[1] == half)
+            || self.quiet_plies >= MAX_QUIET_PLIES
+            || self.list_can_sow().is_empty()
+    }
+
+    /// Final scores, with the seeds left on the board going to the row's owner.
+    pub fn last_scores(&self) -> (u8, u8) {
+        (
+            self.score[0] + self.row_sum(Side::First),
+            self.score[1] + self.row_sum(Side::Second),
+        )
+    }
+}
+
+impl Game for Board {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        self.side.into()
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.is_finished() {
+            Vec::new()
+        } else {
+            self.list_can_sow()
+        }
+    }
+
+    fn apply(&mut self, pos: usize) {
+        self.sow(pos);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        if !self.is_finished() {
+            return None;
+        }
+        Some(final_result(self.last_scores()))
+    }
+
+    fn hash_key(&self) -> u64 {
+        hash_of(self)
+    }
+}
+
+pub trait AI {
+    fn sow(&mut self, board: &Board) -> usize;
+}
+
+pub struct RandomAI<R> {
+    rng: R,
+}
+
+impl<R: Rng> RandomAI<R> {
+    pub fn new(rng: R) -> RandomAI<R> {
+        RandomAI { rng }
+    }
+}
+
+impl<R: Rng> AI for RandomAI<R> {
+    fn sow(&mut self, board: &Board) -> usize {
+        *board.list_can_sow().choose(&mut self.rng).expect("no move")
+    }
+}
+
+/// Negamax with alpha-beta pruning on the captured score difference.
+pub struct DfsAI<R> {
+    rng: R,
+    depth: u32,
+}
+
+impl<R: Rng> DfsAI<R> {
+    pub fn new(rng: R, depth: u32) -> DfsAI<R> {
+        DfsAI { rng, depth }
+    }
+
+    fn evaluate(board: &Board) -> i32 {
+        let me = board.side.index();
+        if board.is_finished() {
+            let (a, b) = board.last_scores();
+            let s = [a as i32, b as i32];
+            s[me] - s[1 - me]
+        } else {
+            board.score[me] as i32 - board.score[1 - me] as i32
+        }
+    }
+
+    fn negamax(&self, board: &Board, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+        if depth == 0 || board.is_finished() {
+            return Self::evaluate(board);
+        }
+        let mut best = i32::MIN;
+        for pos in board.list_can_sow() {
+            let mut next = board.clone();
+            next.sow(pos);
+            let v = -self.negamax(&next, depth - 1, -beta, -alpha);
+            best = best.max(v);
+            alpha = alpha.max(v);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+}
+
+impl<R: Rng> DfsAI<R> {
+    /// The first of `moves` with the best score, and that score.
+    fn search(&self, board: &Board, moves: Vec<usize>) -> (usize, i32) {
+        let mut best = (moves[0], i32::MIN + 1);
+        for pos in moves {
+            let mut next = board.clone();
+            next.sow(pos);
+            let v = -self.negamax(&next, self.depth - 1, i32::MIN + 1, -best.1);
+            if v > best.1 {
+                best = (pos, v);
+            }
+        }
+        best
+    }
+
+    /// The pit to sow with its score, the seeds of the side to move minus those
+    /// of the opponent as far as the search sees. `None` once the game is over.
+    pub fn best(&self, board: &Board) -> Option<(usize, i32)> {
+        if board.is_finished() {
+            return None;
+        }
+        Some(self.search(board, board.list_can_sow()))
+    }
+}
+
+impl<R: Rng> AI for DfsAI<R> {
+    fn sow(&mut self, board: &Board) -> usize {
+        let mut moves = board.list_can_sow();
+        moves.shuffle(&mut self.rng);
+        self.search(board, moves).0
+    }
+}
+
+/// Builds an Oware AI from `"random"` or `"dfs:<depth>"`.
+pub fn build_ai(searcher: &str) -> Result<Box<dyn AI>, String> {
+    build_ai_with_rng(searcher, SmallRng::from_os_rng())
+}
+
+/// [`build_ai`] with random choices drawn from `seed`, for reproducible matches.
+pub fn build_seeded_ai(searcher: &str, seed: u64) -> Result<Box<dyn AI>, String> {
+    build_ai_with_rng(searcher, SmallRng::seed_from_u64(seed))
+}
+
+fn build_ai_with_rng(searcher: &str, rng: SmallRng) -> Result<Box<dyn AI>, String> {
+    let mut words = searcher.split(':');
+    match (words.next(), words.next(), words.next()) {
+        (Some("random"), None, None) => Ok(Box::new(RandomAI::new(rng))),
+        (Some("dfs"), Some(depth), None) => {
+            let depth = depth
+                .parse()
+                .map_err(|e| format!("invalid depth {:?}: {}", depth, e))?;
+            if depth == 0 {
+                return Err("depth must be positive".to_owned());
+            }
+            Ok(Box::new(DfsAI::new(rng, depth)))
+        }
+        _ => Err(format!("unknown searcher: {}", searcher)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_pcg::Pcg32;
+
+    fn board(side: Side, seeds: [[u8; PITS]; 2], score: [u8; 2]) -> Board {
+        Board {
+            side,
+            seeds,
+            score,
+            quiet_plies: 0,
+        }
+    }
+
+    #[test]
+    fn sow_simple() {
+        let mut b = Board::new();
+        b.sow(4);
+        assert_eq!(b.seeds, [[4, 4, 4, 4, 0, 5], [5, 5, 5, 4, 4, 4]]);
+        assert_eq!(b.side, Side::Second);
+    }
+
+    #[test]
+    fn sow_skips_origin() {
+        let mut b = board(Side::First, [[12, 0, 0, 0, 0, 0], [1; PITS]], [0, 0]);
+        b.sow(0);
+        assert_eq!(b.seeds[0], [0, 2, 1, 1, 1, 1]);
+        assert_eq!(b.seeds[1], [2; PITS]);
+    }
+
+    #[test]
+    fn capture_chain() {
+        let mut b = board(
+            Side::First,
+            [[0, 0, 0, 0, 0, 3], [1, 2, 2, 5, 0, 0]],
+            [0, 0],
+        );
+        b.sow(5);
+        assert_eq!(b.seeds[1], [0, 0, 0, 5, 0, 0]);
+        assert_eq!(b.score, [8, 0]);
+    }
+
+    #[test]
+    fn capture_stops_at_gap() {
+        let mut b = board(
+            Side::First,
+            [[0, 0, 0, 0, 0, 3], [4, 1, 2, 5, 0, 0]],
+            [0, 0],
+        );
+        b.sow(5);
+        assert_eq!(b.seeds[1], [5, 0, 0, 5, 0, 0]);
+        assert_eq!(b.score, [5, 0]);
+    }
+
+    #[test]
+    fn grand_slam_captures_nothing() {
+        let mut b = board(
+            Side::First,
+            [[0, 0, 0, 0, 0, 2], [1, 2, 0, 0, 0, 0]],
+            [0, 0],
+        );
+        b.sow(5);
+        assert_eq!(b.seeds[1], [2, 3, 0, 0, 0, 0]);
+        assert_eq!(b.score, [0, 0]);
+    }
+
+    #[test]
+    fn must_feed() {
+        let b = board(Side::First, [[0, 0, 0, 1, 0, 1], [0; PITS]], [22, 24]);
+        assert!(b.can_sow(3).is_err());
+        assert!(b.can_sow(5).is_ok());
+        assert_eq!(b.list_can_sow(), vec![5]);
+
+        let b = board(Side::First, [[2, 0, 0, 1, 0, 0], [0; PITS]], [21, 24]);
+        assert!(b.list_can_sow().is_empty());
+        assert!(b.is_finished());
+        assert_eq!(b.last_scores(), (24, 24));
+        assert_eq!(b.result(), Some(GameResult::Draw));
+        assert!(Game::legal_moves(&b).is_empty());
+    }
+
+    #[test]
+    fn endless_cycle() {
+        // Two lone seeds chase each other around the board forever.
+        let mut b = board(
+            Side::First,
+            [[0, 0, 0, 0, 0, 1], [0, 0, 0, 0, 0, 1]],
+            [23, 23],
+        );
+        let mut plies = 0;
+        while let Some(&pos) = Game::legal_moves(&b).first() {
+            b.apply(pos);
+            plies += 1;
+        }
+        assert_eq!(plies, MAX_QUIET_PLIES);
+        assert_eq!(b.last_scores(), (24, 24));
+        assert_eq!(b.result(), Some(GameResult::Draw));
+
+        // A capture starts the count again.
+        let mut b = board(
+            Side::First,
+            [[0, 0, 0, 0, 0, 3], [1, 2, 2, 5, 0, 0]],
+            [0, 0],
+        );
+        b.quiet_plies = MAX_QUIET_PLIES - 1;
+        b.sow(5);
+        assert_eq!(b.quiet_plies(), 0);
+        assert!(!b.is_finished());
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(Board::new().validate(), Ok(()));
+        let b = board(Side::First, [[2, 0, 0, 1, 0, 0], [0; PITS]], [21, 24]);
+        assert_eq!(b.validate(), Ok(()));
+        let b = board(Side::First, [[200, 0, 0, 0, 0, 0], [1; PITS]], [0, 0]);
+        assert!(b.validate().is_err());
+        let b = board(Side::First, [[4; PITS], [4; PITS]], [0, 1]);
+        assert!(b.validate().is_err());
+        let b = board(Side::Second, [[2, 0, 0, 1, 0, 0], [0; PITS]], [21, 24]);
+        assert!(b.validate().is_err());
+        let mut b = Board::new();
+        b.quiet_plies = MAX_QUIET_PLIES + 1;
+        assert!(b.validate().is_err());
+    }
+
+    #[test]
+    fn game_trait() {
+        let mut b = Board::new();
+        assert_eq!(game::perft(&b, 2), 36);
+        b.apply(4);
+        assert_eq!(b.to_move(), Player::Second);
+        assert_eq!(b.result(), None);
+        assert_ne!(b.hash_key(), Board::new().hash_key());
+    }
+
+    #[test]
+    fn smoke() {
+        for seed in 0..4 {
+            let mut ai: [Box<dyn AI>; 2] = [
+                Box::new(DfsAI::new(Pcg32::seed_from_u64(seed), 4)),
+                Box::new(RandomAI::new(Pcg32::seed_from_u64(seed))),
+            ];
+            let mut b = Board::new();
+            for _ in 0..300 {
+                if b.is_finished() {
+                    break;
+                }
+                let pos = ai[b.side.index()].sow(&b);
+                assert!(b.can_sow(pos).is_ok());
+                b.sow(pos);
+                let (s0, s1) = b.last_scores();
+                assert_eq!(s0 + s1, TOTAL_SEEDS);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_ai() {
+        assert!(build_ai("random").is_ok());
+        assert!(build_ai("dfs:6").is_ok());
+        assert!(build_ai("dfs:0").is_err());
+        assert!(build_ai("dfs").is_err());
+        assert!(build_ai("mcts").is_err());
+    }
+
+    #[test]
+    fn seeded_ai() {
+        let mut b = Board::new();
+        let mut x = build_seeded_ai("random", 3).unwrap();
+        let mut y = build_seeded_ai("random", 3).unwrap();
+        for _ in 0..20 {
+            if b.is_finished() {
+                break;
+            }
+            let pos = x.sow(&b);
+            assert_eq!(y.sow(&b), pos);
+            b.sow(pos);
+        }
+        assert!(build_seeded_ai("dfs:0", 3).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use game::{GameResult, Player};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Side {
+    First,
+    Second,
+}
+
+impl Side {
+    #[inline]
+    pub fn flip(self) -> Side {
+        match self {
+            Side::First => Side::Second,
+            Side::Second => Side::First,
+        }
+    }
+
+    #[inline]
+    pub fn index(self) -> usize {
+        match self {
+            Side::First => 0,
+            Side::Second => 1,
+        }
+    }
+}
+
+impl From<Side> for Player {
+    fn from(side: Side) -> Player {
+        match side {
+            Side::First => Player::First,
+            Side::Second => Player::Second,
+        }
+    }
+}
+
+/// Result of a finished game from its final scores.
+pub(crate) fn final_result((a, b): (u8, u8)) -> GameResult {
+    match a.cmp(&b) {
+        std::cmp::Ordering::Greater => GameResult::Win(Player::First),
+        std::cmp::Ordering::Less => GameResult::Win(Player::Second),
+        std::cmp::Ordering::Equal => GameResult::Draw,
+    }
+}
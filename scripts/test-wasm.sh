@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+# Run the wasm-bindgen tests of the WASM packages, which `cargo test --all`
+# does not build as they only exist for wasm32.
+#
+# Usage:
+#   scripts/test-wasm.sh [cargo test args...]
+#
+# connect-four and tic-tac-toe run under Node.js. mancala runs in a headless
+# browser and needs chromedriver or geckodriver on PATH; set
+# WASM_BINDGEN_TEST_ONLY_NODE=1 to skip it.
+
+set -euo pipefail
+
+ROOT_DIR="$(cd -- "$(dirname -- "${BASH_SOURCE[0]}")/.." && pwd)"
+cd "$ROOT_DIR"
+
+# Same cfg as scripts/build-wasm.sh.
+export RUSTFLAGS="${RUSTFLAGS:-} --cfg getrandom_backend=\"wasm_js\""
+export CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUNNER=wasm-bindgen-test-runner
+
+cargo test --target wasm32-unknown-unknown -p connect-four -p tic-tac-toe -p mancala "$@"
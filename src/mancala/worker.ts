@@ -6,6 +6,9 @@ const wasmModule = {
   async initialize() {
     const wasm = await wasmImport;
     Object.assign(wasmModule, wasm);
+    // The first endgame search would build these tables and stall the game.
+    wasm.initEndgame(6, true);
+    wasm.initEndgame(6, false);
   },
 };
 
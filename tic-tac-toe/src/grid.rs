@@ -0,0 +1,352 @@
+//! Tic-tac-toe on a board of any size up to [`MAX_SIZE`], won by `k` marks in a
+//! row. X moves first.
+use game::{
+    hash_of,
+    mcts::{Mcts, MoveStats, RandomPlayout},
+    negamax::{Negamax, SearchInfo},
+    Game, GameResult, Player,
+};
+
+use std::time::Duration;
+
+use crate::CellType::{self, *};
+
+pub const MAX_SIZE: usize = 8;
+
+/// Directions of a line: right, down, down-right and down-left.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (-1, 1)];
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    k: usize,
+    /// Row by row from the top left.
+    cells: Vec<CellType>,
+    next: CellType,
+    winner: Option<CellType>,
+    filled: usize,
+}
+
+fn player(cell: CellType) -> Player {
+    match cell {
+        X => Player::First,
+        O => Player::Second,
+        E => unreachable!("empty cell has no player"),
+    }
+}
+
+impl Grid {
+    /// An empty board.
+    pub fn new(width: usize, height: usize, k: usize) -> Result<Grid, String> {
+        if !(1..=MAX_SIZE).contains(&width) || !(1..=MAX_SIZE).contains(&height) {
+            return Err(format!(
+                "size must be 1 to {}: {}x{}",
+                MAX_SIZE, width, height
+            ));
+        }
+        if k == 0 || k > width.max(height) {
+            return Err(format!("k does not fit {}x{}: {}", width, height, k));
+        }
+        Ok(Grid {
+            width,
+            height,
+            k,
+            cells: vec![E; width * height],
+            next: X,
+            winner: None,
+            filled: 0,
+        })
+    }
+
+    /// The 3x3 board won by three in a row.
+    pub fn classic() -> Grid {
+        Grid::new(3, 3, 3).unwrap()
+    }
+
+    /// The board after `moves` from an empty one.
+    pub fn from_moves(
+        width: usize,
+        height: usize,
+        k: usize,
+        moves: &[usize],
+    ) -> Result<Grid, String> {
+        let mut grid = Grid::new(width, height, k)?;
+        for &pos in moves {
+            if !grid.can_put(pos) {
+                return Err(format!("cannot put at {}", pos));
+            }
+            grid.put(pos);
+        }
+        Ok(grid)
+    }
+
+    /// A 3x3 board as sent by the web UI, with `next` to move.
+    pub fn from_cells(cells: &[CellType; 9], next: CellType) -> Grid {
+        debug_assert!(next != E);
+        let mut grid = Grid::classic();
+        grid.cells = cells.to_vec();
+        grid.next = next;
+        grid.filled = cells.iter().filter(|&&c| c != E).count();
+        grid.winner = (0..9)
+            .find(|&pos| grid.makes_line(pos))
+            .map(|pos| cells[pos]);
+        grid
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn cells(&self) -> &[CellType] {
+        &self.cells
+    }
+
+    pub fn next(&self) -> CellType {
+        self.next
+    }
+
+    /// The mark with `k` in a row, if any.
+    pub fn winner(&self) -> Option<CellType> {
+        self.winner
+    }
+
+    pub fn can_put(&self, pos: usize) -> bool {
+        self.winner.is_none() && pos < self.cells.len() && self.cells[pos] == E
+    }
+
+    /// Puts the mark of the side to move at `pos`, which must be empty.
+    pub fn put(&mut self, pos: usize) {
+        debug_assert!(self.can_put(pos));
+        self.cells[pos] = self.next;
+        self.filled += 1;
+        if self.makes_line(pos) {
+            self.winner = Some(self.next);
+        }
+        self.next = self.next.flip();
+    }
+
+    /// Heuristic value of an unfinished game for the side to move: each line of
+    /// `k` cells with marks of only one side counts the square of those marks for
+    /// that side.
+    pub fn evaluate(&self) -> i32 {
+        let (width, height, k) = (self.width as isize, self.height as isize, self.k as isize);
+        let mut value = 0;
+        for y in 0..height {
+            for x in 0..width {
+                for &(dx, dy) in &DIRECTIONS {
+                    let (ex, ey) = (x + dx * (k - 1), y + dy * (k - 1));
+                    if !(0..width).contains(&ex) || !(0..height).contains(&ey) {
+                        continue;
+                    }
+                    let mut count = [0; 3];
+                    for i in 0..k {
+                        let pos = (y + dy * i) * width + x + dx * i;
+                        count[self.cells[pos as usize] as usize] += 1;
+                    }
+                    match count {
+                        [_, n, 0] => value += n * n,
+                        [_, 0, n] => value -= n * n,
+                        _ => (),
+                    }
+                }
+            }
+        }
+        match self.next {
+            X => value,
+            _ => -value,
+        }
+    }
+
+    /// Whether the mark at `pos` is part of `k` in a row.
+    fn makes_line(&self, pos: usize) -> bool {
+        let mark = self.cells[pos];
+        if mark == E {
+            return false;
+        }
+        let (x, y) = ((pos % self.width) as isize, (pos / self.width) as isize);
+        let count = |dx: isize, dy: isize| {
+            let mut n = 0;
+            let (mut x, mut y) = (x + dx, y + dy);
+            while 0 <= x
+                && x < self.width as isize
+                && 0 <= y
+                && y < self.height as isize
+                && self.cells[y as usize * self.width + x as usize] == mark
+            {
+                n += 1;
+                x += dx;
+                y += dy;
+            }
+            n
+        };
+        DIRECTIONS
+            .iter()
+            .any(|&(dx, dy)| 1 + count(dx, dy) + count(-dx, -dy) >= self.k)
+    }
+}
+
+impl Game for Grid {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        player(self.next)
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.winner.is_some() {
+            return Vec::new();
+        }
+        (0..self.cells.len())
+            .filter(|&pos| self.cells[pos] == E)
+            .collect()
+    }
+
+    fn apply(&mut self, pos: usize) {
+        self.put(pos);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        match self.winner {
+            Some(mark) => Some(GameResult::Win(player(mark))),
+            None if self.filled == self.cells.len() => Some(GameResult::Draw),
+            None => None,
+        }
+    }
+
+    fn hash_key(&self) -> u64 {
+        hash_of(&(&self.cells, self.next))
+    }
+}
+
+/// Best move of the generic MCTS engine after `iterations` random playouts, for
+/// boards too large for the exhaustive [`search`](crate::search). `None` once the
+/// game is over.
+pub fn mcts(grid: &Grid, iterations: u32, c: f64, seed: u64) -> Option<MoveStats<usize>> {
+    let mut mcts = Mcts::new(grid, RandomPlayout::new(seed), c);
+    mcts.run(iterations);
+    mcts.best()
+}
+
+/// Alpha-beta search up to `depth` plies or for `limit`, scored by
+/// [`Grid::evaluate`].
+pub fn alpha_beta(grid: &Grid, depth: u32, limit: Duration) -> SearchInfo<usize> {
+    Negamax::new(Grid::evaluate, depth)
+        .with_time_limit(limit)
+        .search(grid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_winner;
+
+    #[test]
+    fn new_checks_size() {
+        assert!(Grid::new(0, 3, 3).is_err());
+        assert!(Grid::new(9, 3, 3).is_err());
+        assert!(Grid::new(3, 3, 4).is_err());
+        assert!(Grid::new(5, 4, 5).is_ok());
+        assert!(Grid::from_moves(3, 3, 3, &[4, 4]).is_err());
+        assert!(Grid::from_moves(3, 3, 3, &[0, 3, 1, 4, 2, 5]).is_err());
+    }
+
+    #[test]
+    fn perft_classic() {
+        // Number of distinct tic-tac-toe games.
+        assert_eq!(game::perft(&Grid::classic(), 9), 255168);
+    }
+
+    #[test]
+    fn from_cells() {
+        for cells in [
+            [X, X, X, E, O, O, E, E, E],
+            [X, O, E, O, X, E, E, E, X],
+            [O, O, X, X, X, O, O, X, X],
+            [E; 9],
+        ] {
+            let grid = Grid::from_cells(&cells, O);
+            let expected = calculate_winner(&cells).filter(|&c| c != E);
+            assert_eq!(grid.winner(), expected);
+        }
+        let grid = Grid::from_cells(&[O, O, X, X, X, O, O, X, X], O);
+        assert_eq!(grid.result(), Some(GameResult::Draw));
+    }
+
+    #[test]
+    fn larger_board() {
+        let mut grid = Grid::new(4, 4, 3).unwrap();
+        // X on the anti-diagonal from (3, 0), O on the top left.
+        for pos in [3, 0, 6, 1, 9] {
+            assert_eq!(grid.result(), None);
+            grid.apply(pos);
+        }
+        assert_eq!(grid.result(), Some(GameResult::Win(Player::First)));
+        assert!(grid.legal_moves().is_empty());
+        assert!(!grid.can_put(15));
+    }
+
+    #[test]
+    fn alpha_beta_classic() {
+        let limit = Duration::from_secs(10);
+        let info = alpha_beta(&Grid::classic(), 9, limit);
+        assert!(info.best.is_some());
+        assert_eq!(info.score, 0);
+
+        let grid = Grid::from_cells(&[X, X, E, O, O, E, E, E, E], O);
+        let info = alpha_beta(&grid, 9, limit);
+        assert_eq!(info.best, Some(5));
+        assert_eq!(game::negamax::mate_plies(info.score), Some(1));
+        // The fork of X wins in 5 plies whatever O does.
+        let grid = Grid::from_cells(&[X, O, E, E, X, E, E, E, E], O);
+        let info = alpha_beta(&grid, 9, limit);
+        assert_eq!(info.best, Some(8));
+        assert!(info.score < 0);
+    }
+
+    #[test]
+    fn alpha_beta_large_board() {
+        let mut grid = Grid::new(5, 5, 4).unwrap();
+        for pos in [6, 5, 7, 24, 8] {
+            grid.apply(pos);
+        }
+        assert!(grid.evaluate() < 0);
+        let limit = Duration::from_secs(10);
+        assert_eq!(alpha_beta(&grid, 4, limit).best, Some(9));
+        grid.apply(0);
+        let info = alpha_beta(&grid, 4, limit);
+        assert_eq!(info.best, Some(9));
+        assert_eq!(game::negamax::mate_plies(info.score), Some(1));
+    }
+
+    #[test]
+    fn mcts_large_board() {
+        let mut grid = Grid::new(5, 5, 4).unwrap();
+        // X has three in a row on the second row with the left end taken by O, so
+        // O must take the right end.
+        for pos in [6, 5, 7, 24, 8] {
+            grid.apply(pos);
+        }
+        let best = mcts(&grid, 20000, 1.4, 1).unwrap();
+        assert_eq!(best.mv, 9);
+
+        // Otherwise X wins at once.
+        grid.apply(0);
+        let best = mcts(&grid, 20000, 1.4, 1).unwrap();
+        assert_eq!(best.mv, 9);
+        assert_eq!(
+            best.proof.map(|p| p.result),
+            Some(GameResult::Win(Player::First))
+        );
+        grid.apply(9);
+        assert!(mcts(&grid, 10, 1.4, 1).is_none());
+    }
+}
@@ -1,3 +1,11 @@
+pub mod grid;
+
+use game::{
+    analysis::{analyze, mcts_evaluation, Annotation, Evaluation, Thresholds},
+    history::History,
+    mcts::{Mcts, RandomPlayout},
+    record::GameRecord,
+};
 use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{from_value, to_value};
@@ -5,7 +13,7 @@ use wasm_bindgen::prelude::*;
 
 use CellType::*;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum CellType {
     E,
     X,
@@ -58,6 +66,17 @@ pub struct SearchResponse {
     pub score: i32,
 }
 
+fn gen_seed() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    let seed = (js_sys::Math::random() * 2f64.powi(64)) as u64;
+    #[cfg(not(target_arch = "wasm32"))]
+    let seed = {
+        use rand::RngCore;
+        rand::thread_rng().next_u64()
+    };
+    seed
+}
+
 pub fn search(board: &[CellType; 9], next: CellType) -> SearchResponse {
     debug_assert!(next != E);
 
@@ -110,14 +129,7 @@ pub fn search(board: &[CellType; 9], next: CellType) -> SearchResponse {
         }
     }
 
-    #[cfg(target_arch = "wasm32")]
-    let seed = (js_sys::Math::random() * 2f64.powi(64)) as u64;
-    #[cfg(not(target_arch = "wasm32"))]
-    let seed = {
-        use rand::RngCore;
-        rand::thread_rng().next_u64()
-    };
-    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut rng = SmallRng::seed_from_u64(gen_seed());
     dfs(&mut rng, &mut board.clone(), next)
 }
 
@@ -128,6 +140,155 @@ pub fn js_search(board: &JsValue, next: &str) -> Result<JsValue, JsValue> {
     Ok(to_value(&search(&board, next))?)
 }
 
+#[derive(Debug, Serialize)]
+pub struct GridSearchResponse {
+    pub position: Option<u32>,
+    /// Mean point of the move for the side to move, 1 for a win and 0 for a loss.
+    pub point: f64,
+}
+
+/// MCTS move on a `width`x`height` board won by `k` in a row, after the cells in
+/// `moves` were played in turn from the empty board.
+#[wasm_bindgen(js_name = gridSearch)]
+pub fn js_grid_search(
+    width: u32,
+    height: u32,
+    k: u32,
+    moves: &[u32],
+    iterations: u32,
+    c: f64,
+) -> Result<JsValue, JsValue> {
+    let moves = moves.iter().map(|&pos| pos as usize).collect::<Vec<_>>();
+    let grid = grid::Grid::from_moves(width as usize, height as usize, k as usize, &moves)
+        .map_err(|e| JsValue::from_str(&e))?;
+    let best = grid::mcts(&grid, iterations, c, gen_seed());
+    Ok(to_value(&GridSearchResponse {
+        position: best.as_ref().map(|b| b.mv as u32),
+        point: best.map_or(0.0, |b| b.point),
+    })?)
+}
+
+/// Rules of a [`Record`]: the board size and the marks in a row to win.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub width: usize,
+    pub height: usize,
+    pub k: usize,
+}
+
+impl Variant {
+    pub fn classic() -> Variant {
+        Variant {
+            width: 3,
+            height: 3,
+            k: 3,
+        }
+    }
+}
+
+/// A played game, with the cell index of each move.
+pub type Record = GameRecord<Variant, usize>;
+
+pub const RECORD_GAME: &str = "tic-tac-toe";
+
+fn record_start(variant: &Variant) -> Result<grid::Grid, String> {
+    grid::Grid::new(variant.width, variant.height, variant.k)
+}
+
+/// Reads a [`Record`], checking its moves and result.
+pub fn parse_record(json: &str) -> Result<Record, String> {
+    Record::parse(json, RECORD_GAME, record_start)
+}
+
+/// Writes a [`Record`] after the checks of [`parse_record`].
+pub fn emit_record(record: &Record) -> Result<String, String> {
+    record.emit(RECORD_GAME, record_start)
+}
+
+#[wasm_bindgen(js_name = parseRecord)]
+pub fn js_parse_record(json: &str) -> Result<JsValue, JsValue> {
+    let record = parse_record(json).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&record)?)
+}
+
+#[wasm_bindgen(js_name = emitRecord)]
+pub fn js_emit_record(record: &JsValue) -> Result<String, JsValue> {
+    let record: Record = from_value(record.clone())?;
+    emit_record(&record).map_err(|e| JsValue::from_str(&e))
+}
+
+/// A game from the empty board with its moves, so that the frontend can undo,
+/// redo and jump to any ply without replaying the moves itself.
+#[wasm_bindgen]
+pub struct GameHistory {
+    history: History<grid::Grid>,
+}
+
+#[wasm_bindgen]
+impl GameHistory {
+    /// A `width`x`height` board won by `k` in a row.
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32, k: u32) -> Result<GameHistory, JsValue> {
+        let grid = grid::Grid::new(width as usize, height as usize, k as usize)
+            .map_err(|e| JsValue::from_str(&e))?;
+        Ok(GameHistory {
+            history: History::new(grid),
+        })
+    }
+
+    /// The cells of the current board row by row, `"E"`, `"X"` or `"O"`.
+    pub fn board(&self) -> Result<JsValue, JsValue> {
+        Ok(to_value(self.history.current().cells())?)
+    }
+}
+
+game::history_methods! {
+    GameHistory,
+    /// Marks the cell `pos` for the side to move.
+    play(pos)
+}
+
+/// Annotates every move of `record`, by the perfect [`search`] on the classic
+/// board and by MCTS of `iterations` per position on the others.
+pub fn analyze_game(record: &Record, iterations: u32) -> Result<Vec<Annotation<usize>>, String> {
+    let start = record_start(&record.variant)?;
+    let moves = record.move_list();
+    let thresholds = Thresholds::default();
+    if record.variant == Variant::classic() {
+        return analyze(
+            &start,
+            &moves,
+            |grid| {
+                let cells: [CellType; 9] = grid.cells().try_into().unwrap();
+                let r = search(&cells, grid.next());
+                Evaluation {
+                    point: (r.score.signum() + 1) as f64 / 2.0,
+                    best: r.position.map(|pos| pos as usize),
+                }
+            },
+            &thresholds,
+        );
+    }
+    let mut rng = SmallRng::seed_from_u64(record.seed.unwrap_or_else(gen_seed));
+    analyze(
+        &start,
+        &moves,
+        |grid| {
+            let mut mcts = Mcts::new(grid, RandomPlayout::new(rng.gen()), 1.4);
+            mcts.run(iterations);
+            mcts_evaluation(&mcts)
+        },
+        &thresholds,
+    )
+}
+
+#[wasm_bindgen(js_name = analyzeGame)]
+pub fn js_analyze_game(record: &JsValue, iterations: u32) -> Result<JsValue, JsValue> {
+    let record: Record = from_value(record.clone())?;
+    let annotations = analyze_game(&record, iterations).map_err(|e| JsValue::from_str(&e))?;
+    Ok(to_value(&annotations)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -168,6 +329,59 @@ mod tests {
         assert!(r.score < 0);
     }
 
+    #[test]
+    fn record() {
+        let json = r#"{
+            "game": "tic-tac-toe",
+            "variant": {"width": 3, "height": 3, "k": 3},
+            "players": [{"name": "X"}, {"ai": "search"}],
+            "moves": [{"move": 4}, {"move": 0}, {"move": 2}, {"move": 6}, {"move": 3},
+                      {"move": 5}, {"move": 1}, {"move": 7}, {"move": 8}],
+            "result": "draw",
+            "startedAt": 1700000000000
+        }"#;
+        let record = parse_record(json).unwrap();
+        assert_eq!(record.variant, Variant::classic());
+        assert_eq!(record.move_list()[..3], [4, 0, 2]);
+        assert_eq!(
+            parse_record(&emit_record(&record).unwrap()).unwrap(),
+            record
+        );
+        assert!(parse_record(&json.replace(r#""draw""#, r#""first""#)).is_err());
+        assert!(parse_record(&json.replace(r#""k": 3"#, r#""k": 4"#)).is_err());
+    }
+
+    #[test]
+    fn analysis() {
+        use game::analysis::Classification;
+
+        let mut record = Record::new(RECORD_GAME, Variant::classic());
+        record.moves = [4, 1]
+            .map(|mv| game::record::MoveRecord {
+                mv,
+                eval: None,
+                at: None,
+            })
+            .to_vec();
+        let annotations = analyze_game(&record, 0).unwrap();
+        assert_eq!(annotations[0].classification, Classification::Best);
+        // An edge against the center loses.
+        assert_eq!(annotations[1].before, 0.5);
+        assert_eq!(annotations[1].after, 0.0);
+        assert_eq!(annotations[1].classification, Classification::Blunder);
+        assert!([0, 2, 6, 8].contains(&annotations[1].best.unwrap()));
+
+        record.variant = Variant {
+            width: 4,
+            height: 4,
+            k: 3,
+        };
+        record.seed = Some(1);
+        assert_eq!(analyze_game(&record, 100).unwrap().len(), 2);
+        record.moves[1].mv = 4;
+        assert!(analyze_game(&record, 100).is_err());
+    }
+
     #[cfg(target_arch = "wasm32")]
     #[wasm_bindgen_test]
     fn test_js_search() {
@@ -175,4 +389,26 @@ mod tests {
             js_sys::JSON::parse(r#"["X", "X", "E", "O", "O", "E", "E", "E", "E"]"#).unwrap();
         js_search(&board, "X").unwrap();
     }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_js_grid_search() {
+        js_grid_search(4, 4, 3, &[0, 5], 100, 1.4).unwrap();
+        assert!(js_grid_search(4, 4, 3, &[0, 0], 100, 1.4).is_err());
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn history() {
+        let mut history = GameHistory::new(4, 4, 3).unwrap();
+        history.play(5).unwrap();
+        assert!(history.play(5).is_err());
+        assert!(history.undo());
+        assert!(!history.undo());
+        history.play(0).unwrap();
+        assert!(!history.redo());
+        let cells: Vec<CellType> = from_value(history.board().unwrap()).unwrap();
+        assert_eq!(cells[0], X);
+        history.status().unwrap();
+    }
 }